@@ -0,0 +1,108 @@
+use crate::report::BenchmarkRecord;
+use crate::stats::welch_t_test;
+use failure::{format_err, Error};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Differences with a p-value below this are considered significant.
+const SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+fn baseline_path(directory: &Path, name: &str) -> PathBuf {
+    directory.join(format!("{}.json", name))
+}
+
+// Results of the same test with e.g. a different cache mode aren't comparable. Baselines saved
+// before records had their suite match any suite.
+fn same_test(a: &BenchmarkRecord, b: &BenchmarkRecord) -> bool {
+    (a.suite.is_empty() || b.suite.is_empty() || a.suite == b.suite)
+        && a.name == b.name
+        && a.parameters == b.parameters
+}
+
+pub fn load(directory: &Path, name: &str) -> Result<Vec<BenchmarkRecord>, Error> {
+    let path = baseline_path(directory, name);
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format_err!("failed to read baseline {}: {}", path.display(), e))?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+// Saving a run of only some of the tests keeps the results of the other tests in the baseline. A
+// baseline that exists but can't be read is an error rather than something to overwrite.
+pub fn save(directory: &Path, name: &str, records: &[BenchmarkRecord]) -> Result<(), Error> {
+    let mut baseline = if baseline_path(directory, name).exists() {
+        load(directory, name)?
+    } else {
+        vec![]
+    };
+    baseline.retain(|old| !records.iter().any(|new| same_test(old, new)));
+    baseline.extend_from_slice(records);
+
+    fs::create_dir_all(directory)?;
+    fs::write(
+        baseline_path(directory, name),
+        serde_json::to_string_pretty(&baseline)?,
+    )?;
+    Ok(())
+}
+
+pub struct Comparison {
+    pub name: String,
+    pub parameters: BTreeMap<String, String>,
+    pub baseline_ns: f64,
+    // `None` when the test is in the baseline but didn't run this time, e.g. because it failed.
+    pub current_ns: Option<f64>,
+    // `None` when either run had too few samples to test, e.g. `sort` which only runs once. Those
+    // comparisons are never significant, so they can't regress.
+    pub p_value: Option<f64>,
+    pub regressed: bool,
+}
+
+impl Comparison {
+    pub fn change(&self) -> Option<f64> {
+        self.current_ns
+            .map(|current_ns| (current_ns - self.baseline_ns) / self.baseline_ns * 100.0)
+    }
+
+    pub fn significant(&self) -> bool {
+        self.p_value.is_some_and(|p| p < SIGNIFICANCE_LEVEL)
+    }
+}
+
+// Compares the mean time per iteration of every test that's in both runs. A test regressed when it
+// got slower by more than `threshold` percent, and the difference is significant. Tests of the
+// `suites` that ran which are in the baseline but not in this run are compared as missing.
+pub fn compare(
+    baseline: &[BenchmarkRecord],
+    records: &[BenchmarkRecord],
+    suites: &[&str],
+    threshold: f64,
+) -> Vec<Comparison> {
+    let compared = records.iter().filter_map(|record| {
+        let old = baseline.iter().find(|old| same_test(old, record))?;
+        let mut comparison = Comparison {
+            name: record.name.clone(),
+            parameters: record.parameters.clone(),
+            baseline_ns: old.statistics.mean,
+            current_ns: Some(record.statistics.mean),
+            p_value: welch_t_test(&old.statistics, &record.statistics),
+            regressed: false,
+        };
+        comparison.regressed = comparison.change().is_some_and(|change| change > threshold)
+            && comparison.significant();
+        Some(comparison)
+    });
+    let missing = baseline
+        .iter()
+        .filter(|old| suites.contains(&old.suite.as_str()))
+        .filter(|old| !records.iter().any(|record| same_test(old, record)))
+        .map(|old| Comparison {
+            name: old.name.clone(),
+            parameters: old.parameters.clone(),
+            baseline_ns: old.statistics.mean,
+            current_ns: None,
+            p_value: None,
+            regressed: false,
+        });
+    compared.chain(missing).collect()
+}
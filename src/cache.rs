@@ -0,0 +1,161 @@
+use failure::{format_err, Error};
+use std::alloc::{self, Layout};
+use std::fmt;
+use std::fs;
+use std::fs::OpenOptions;
+use std::ops::{Deref, DerefMut};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::slice;
+
+// Whether the disk suites read their file from the page cache or the disk.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CacheMode {
+    // The file is evicted from the page cache after it's written, and read through it.
+    Cold,
+    // The file is read right after it's written, so it's all in the page cache.
+    Warm,
+    // The file is read with O_DIRECT, bypassing the page cache entirely.
+    Direct,
+}
+
+impl CacheMode {
+    pub fn from_name(name: &str) -> Result<CacheMode, Error> {
+        match name {
+            "cold" => Ok(CacheMode::Cold),
+            "warm" => Ok(CacheMode::Warm),
+            "direct" => Ok(CacheMode::Direct),
+            _ => Err(format_err!(
+                "unknown cache mode {}, expected cold, warm or direct",
+                name
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CacheMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CacheMode::Cold => "cold",
+            CacheMode::Warm => "warm",
+            CacheMode::Direct => "direct",
+        };
+        f.write_str(name)
+    }
+}
+
+// Opens a file that has been written and synced for reading in `mode`, evicting it from the page
+// cache first unless it's supposed to be warm. With `drop_caches`, the whole page cache is dropped
+// too, see `drop_caches`.
+pub fn open_for_reading(
+    path: &Path,
+    mode: CacheMode,
+    drop_caches: bool,
+) -> Result<fs::File, Error> {
+    let mut options = OpenOptions::new();
+    options.read(true);
+    if mode == CacheMode::Direct {
+        #[cfg(target_os = "linux")]
+        options.custom_flags(libc::O_DIRECT);
+    }
+    let file = options.open(path)?;
+
+    if mode != CacheMode::Warm {
+        evict_file(&file)?;
+        if drop_caches {
+            self::drop_caches()?;
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        if mode == CacheMode::Direct {
+            unsafe {
+                libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1);
+            }
+        }
+    }
+    Ok(file)
+}
+
+pub const DROP_CACHES: &str = "/proc/sys/vm/drop_caches";
+
+// Drops the whole page cache, which also gets rid of the filesystem's metadata, for
+// `--drop-caches`. It takes a global sync and flushes every other process's cached files too, so
+// it's only done when asked for, as root, and before the measurement.
+pub fn drop_caches() -> Result<(), Error> {
+    unsafe {
+        libc::sync();
+    }
+    fs::write(DROP_CACHES, "1").map_err(|e| format_err!("failed to drop the page cache: {}", e))
+}
+
+// Drops the file's pages from the page cache. Dirty pages can't be dropped, so the file has to be
+// synced first.
+pub fn evict_file(file: &fs::File) -> Result<(), Error> {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let result = libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+        if result != 0 {
+            return Err(format_err!(
+                "failed to evict file from the page cache: {}",
+                std::io::Error::from_raw_os_error(result)
+            ));
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = file;
+    Ok(())
+}
+
+// A zeroed buffer aligned to the page size, which O_DIRECT requires of the memory it reads into.
+pub struct AlignedBuffer {
+    pointer: *mut u8,
+    layout: Layout,
+}
+
+// The buffer owns its memory, like a `Vec<u8>`.
+unsafe impl Send for AlignedBuffer {}
+
+impl AlignedBuffer {
+    pub fn new(size: usize) -> AlignedBuffer {
+        let layout = Layout::from_size_align(size.max(1), page_size::get()).unwrap();
+        let pointer = unsafe { alloc::alloc_zeroed(layout) };
+        if pointer.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        AlignedBuffer { pointer, layout }
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.pointer, self.layout.size()) }
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.pointer, self.layout.size()) }
+    }
+}
+
+impl AsRef<[u8]> for AlignedBuffer {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl AsMut<[u8]> for AlignedBuffer {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.pointer, self.layout) }
+    }
+}
@@ -0,0 +1,351 @@
+use crate::options::BenchmarkOptions;
+use crate::stats::Statistics;
+use crate::units::{format_metric, format_nanoseconds, get_appropriate_time_unit, metric_label};
+use byte_unit::Byte;
+use failure::Error;
+use num_format::{Locale, ToFormattedString};
+use std::mem::forget;
+use std::ptr;
+use std::time::{Duration, Instant};
+
+// TODO: use this instead
+// from bencher::black_box, avoid compiler dead-code optimizations.
+pub fn black_box<T>(dummy: T) -> T {
+    unsafe {
+        let ret = ptr::read_volatile(&dummy);
+        forget(dummy);
+        ret
+    }
+}
+
+// TODO: Probably we should just expose duration and iterations, and correct for duration_ratio
+// directly in whatever produces this data structure to simplify consumption.
+pub struct BenchmarkResult {
+    pub name: String,
+    pub bytes_per_iteration: usize,
+    pub iterations: usize,
+    pub duration: Duration,
+    // duration_ratio: f64,
+    // intended_duration: Duration,
+    pub cycles: u64,
+    // Time per iteration of the batches timed during the run.
+    pub statistics: Statistics,
+    // Options the result depends on, e.g. `cache=cold`.
+    pub parameters: Vec<(String, String)>,
+    // Whatever else the benchmark measured, e.g. `iops`. Names ending in `_ns` are durations, and
+    // names ending in `_bytes_per_second` throughputs.
+    pub metrics: Vec<(String, f64)>,
+}
+
+impl BenchmarkResult {
+    // Names the result after the benchmark it came from, `benchmark` itself doesn't know.
+    pub fn labelled(mut self, name: &str, bytes_per_iteration: usize) -> BenchmarkResult {
+        self.name = name.to_string();
+        self.bytes_per_iteration = bytes_per_iteration;
+        self
+    }
+
+    pub fn with_parameter(mut self, name: &str, value: &str) -> BenchmarkResult {
+        self.parameters.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_metric(mut self, name: &str, value: f64) -> BenchmarkResult {
+        self.metrics.push((name.to_string(), value));
+        self
+    }
+
+    pub fn print_results(&self) {
+        let size_of_type = self.bytes_per_iteration;
+        let mut name = self.name.clone();
+        if size_of_type > 0 {
+            name.push_str(&format!(
+                " <{}>",
+                Byte::from_bytes(size_of_type as u128)
+                    .get_appropriate_unit(true)
+                    .format(0)
+            ));
+        }
+
+        println!(
+            "\n[{}] Iterations in {} miliseconds, no overhead: {}",
+            name,
+            self.duration.as_millis(),
+            self.iterations.to_formatted_string(&Locale::en)
+        );
+
+        if !self.parameters.is_empty() {
+            let parameters: Vec<String> = self
+                .parameters
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            println!("[{}] Parameters: {}", name, parameters.join(", "));
+        }
+
+        println!(
+            "[{}] Iterations / second: {}",
+            name,
+            (self.iterations_per_second() as u128).to_formatted_string(&Locale::en)
+        );
+
+        if size_of_type > 0 {
+            println!(
+                "[{}] Bytes handled per iteration: {} bytes",
+                name, size_of_type
+            );
+
+            let total_bytes_pushed = size_of_type * self.iterations;
+            println!(
+                "[{}] Total bytes processed: {}",
+                name,
+                Byte::from_bytes(total_bytes_pushed as u128)
+                    .get_appropriate_unit(true)
+                    .format(3)
+            );
+
+            let bytes_per_second = self.iterations_per_second() * size_of_type as f64;
+            println!(
+                "[{}] Throughput: {}/s",
+                name,
+                // TODO: Too hard to get right when values aren't just printed!
+                Byte::from_bytes(bytes_per_second as u128)
+                    .get_appropriate_unit(true)
+                    .format(3)
+            );
+        }
+
+        println!(
+            "[{}] Avg single iteration: {}",
+            name,
+            format_nanoseconds(self.nanoseconds_per_iteration())
+        );
+
+        let stats = &self.statistics;
+        println!(
+            "[{}] Single iteration over {} samples: min {}, median {}, p90 {}, p99 {}, max {}",
+            name,
+            stats.samples,
+            format_nanoseconds(stats.min),
+            format_nanoseconds(stats.median),
+            format_nanoseconds(stats.p90),
+            format_nanoseconds(stats.p99),
+            format_nanoseconds(stats.max),
+        );
+
+        println!(
+            "[{}] Single iteration std. dev: {}, 95% confidence interval of the mean: [{}, {}]",
+            name,
+            format_nanoseconds(stats.std_dev),
+            format_nanoseconds(stats.confidence_interval.0),
+            format_nanoseconds(stats.confidence_interval.1),
+        );
+
+        if stats.outliers.total() > 0 {
+            println!(
+                "[{}] Found {} outliers among {} samples ({:.2}%): {} low severe, {} low mild, {} high mild, {} high severe",
+                name,
+                stats.outliers.total(),
+                stats.samples,
+                stats.outliers.total() as f64 / stats.samples as f64 * 100.0,
+                stats.outliers.low_severe,
+                stats.outliers.low_mild,
+                stats.outliers.high_mild,
+                stats.outliers.high_severe,
+            );
+        }
+
+        let single_operation_cycles = self.cycles as f64 / (self.iterations as f64);
+
+        println!(
+            "[{}] Avg single iteration cycles: {:.2}",
+            name, single_operation_cycles,
+        );
+
+        for (metric, value) in &self.metrics {
+            println!(
+                "[{}] {}: {}",
+                name,
+                metric_label(metric),
+                format_metric(metric, *value)
+            );
+        }
+
+        if size_of_type > 0 {
+            let nanoseconds_per_byte = self.nanoseconds_per_byte(size_of_type);
+            let nanoseconds_per_mebibyte = nanoseconds_per_byte * n_mib_bytes!(1) as f64;
+            let duration_per_mebibyte = Duration::from_nanos(nanoseconds_per_mebibyte as u64);
+
+            println!(
+                "[{}] Time to process 1 MiB: {}",
+                name,
+                get_appropriate_time_unit(duration_per_mebibyte),
+            );
+
+            let nanoseconds_per_gibibyte = nanoseconds_per_byte * n_gib_bytes!(1) as f64;
+            let duration_per_gibibyte = Duration::from_nanos(nanoseconds_per_gibibyte as u64);
+
+            println!(
+                "[{}] Time to process 1 GiB: {}",
+                name,
+                get_appropriate_time_unit(duration_per_gibibyte),
+            );
+
+            let nanoseconds_per_tibibyte = nanoseconds_per_byte * n_tib_bytes!(1) as f64;
+            let duration_per_tibibyte = Duration::from_nanos(nanoseconds_per_tibibyte as u64);
+
+            println!(
+                "[{}] Time to process 1 TiB: {}",
+                name,
+                get_appropriate_time_unit(duration_per_tibibyte),
+            );
+        }
+    }
+
+    pub fn nanoseconds_per_iteration(&self) -> f64 {
+        self.duration.as_nanos() as f64 / self.iterations as f64
+    }
+
+    pub fn iterations_per_second(&self) -> f64 {
+        self.iterations as f64 / self.duration.as_secs_f64()
+    }
+
+    pub fn nanoseconds_per_byte(&self, size_of_type: usize) -> f64 {
+        self.nanoseconds_per_iteration() / size_of_type as f64
+    }
+}
+
+// Roughly how many batches the measurement is split into, each one timed as a sample.
+const TARGET_SAMPLES: usize = 100;
+// Past this many samples, adjacent ones are merged, so a run that keeps going for `min_iterations`
+// doesn't pile up samples for the statistics to chew through.
+const MAX_SAMPLES: usize = 10 * TARGET_SAMPLES;
+
+pub fn benchmark<T, F: Fn() -> Result<T, Error>, V: FnMut(&mut T) -> bool>(
+    options: &BenchmarkOptions,
+    setup: F,
+    f: V,
+) -> Result<BenchmarkResult, Error> {
+    benchmark_with_state(options, setup, f).map(|(result, _)| result)
+}
+
+// Like `benchmark`, but also hands back the state the measurement ended with, for benchmarks that
+// measure more than the time per iteration themselves.
+pub fn benchmark_with_state<T, F: Fn() -> Result<T, Error>, V: FnMut(&mut T) -> bool>(
+    options: &BenchmarkOptions,
+    setup: F,
+    mut f: V,
+) -> Result<(BenchmarkResult, T), Error> {
+    // warmup run, of at least one iteration so there's something to size the batches from
+    let mut val = setup()?;
+    let intended_duration = options.warmup;
+    let max_iterations = options.max_iterations.unwrap_or(usize::MAX);
+    let mut iterations: usize = 0;
+    let instant = Instant::now();
+
+    // The reason for the "done" and boolean return type here is that some benchmarks may want to
+    // finish earlier, e.g. random disk reads want to finish as soon as it's read every page since
+    // otherwise we're just benchmarking memory. If this is the only use-case, maybe we should just
+    // make sure this never happens.
+    let mut done = false;
+    while (iterations == 0 || instant.elapsed() < intended_duration) && iterations < max_iterations
+    {
+        iterations += 1;
+        if !f(&mut val) {
+            done = true;
+            break;
+        }
+    }
+    let warmup_duration = instant.elapsed();
+
+    // real run, with the warmup's state freed first so there's only ever one of them in memory
+    drop(val);
+    let mut val = setup()?;
+    let rdtsc_before: u64;
+    let intended_duration = options.measure;
+
+    // Each check is timed as one sample, so size them to get roughly `TARGET_SAMPLES` out of the
+    // run. Benchmarks that finished early in the warmup will finish early again, so split the work
+    // they got through instead. Otherwise the warmup's estimate is only a start: batches double
+    // for as long as they take less than half of `sample_duration`, in case the warmup was too
+    // short to tell.
+    let sample_duration = intended_duration / TARGET_SAMPLES as u32;
+    let calibrate = !done;
+    let mut iterations_per_check = if done {
+        iterations / TARGET_SAMPLES
+    } else {
+        (iterations as f64 * sample_duration.as_secs_f64() / warmup_duration.as_secs_f64()) as usize
+    }
+    .max(1);
+    let mut iterations: usize = 0;
+    // The time and number of iterations of every batch.
+    let mut batches: Vec<(Duration, usize)> = Vec::with_capacity(TARGET_SAMPLES);
+    let mut last_check = Duration::from_secs(0);
+    let instant = Instant::now();
+    unsafe {
+        rdtsc_before = core::arch::x86_64::_rdtsc();
+    }
+
+    let mut done = false;
+    while (last_check < intended_duration || iterations < options.min_iterations)
+        && iterations < max_iterations
+    {
+        let mut batch = iterations_per_check.min(max_iterations - iterations);
+        for i in 1..(batch + 1) {
+            // unlikely branch
+            if !f(&mut val) {
+                done = true;
+                batch = i;
+                break;
+            }
+        }
+        iterations += batch;
+
+        let elapsed = instant.elapsed();
+        batches.push((elapsed - last_check, batch));
+        if calibrate && elapsed - last_check < sample_duration / 2 {
+            iterations_per_check = iterations_per_check.saturating_mul(2);
+        }
+        last_check = elapsed;
+
+        if batches.len() >= MAX_SAMPLES {
+            batches = batches
+                .chunks(2)
+                .map(|pair| {
+                    pair.iter().fold((Duration::from_secs(0), 0), |sum, batch| {
+                        (sum.0 + batch.0, sum.1 + batch.1)
+                    })
+                })
+                .collect();
+            iterations_per_check = iterations_per_check.saturating_mul(2);
+        }
+
+        if done {
+            break;
+        }
+    }
+    let actual_duration = last_check;
+    let samples: Vec<f64> = batches
+        .iter()
+        .map(|(duration, batch)| duration.as_nanos() as f64 / *batch as f64)
+        .collect();
+
+    let rdtsc_after: u64;
+    unsafe {
+        rdtsc_after = core::arch::x86_64::_rdtsc();
+    }
+
+    let result = BenchmarkResult {
+        name: String::new(),
+        bytes_per_iteration: 0,
+        iterations,
+        duration: actual_duration,
+        // duration_ratio: intended_duration.as_nanos() as f64 / actual_duration.as_nanos() as f64,
+        // intended_duration: intended_duration,
+        cycles: rdtsc_after - rdtsc_before,
+        statistics: Statistics::new(&samples),
+        parameters: vec![],
+        metrics: vec![],
+    };
+    Ok((result, val))
+}
@@ -0,0 +1,27 @@
+// The benchmark harness and the built-in suites behind the `base-rates` binary, so other programs
+// can run the suites or benchmark their own code the same way:
+//
+//   let mut registry = napkin_math::suites::builtin();
+//   registry.register(MyBenchmark);
+//   for suite in registry.suites() {
+//       suite.run(&BenchmarkOptions::default())?;
+//   }
+#[macro_use]
+extern crate byte_unit;
+
+pub mod baseline;
+pub mod cache;
+pub mod harness;
+pub mod mmap;
+pub mod options;
+pub mod registry;
+pub mod report;
+pub mod resources;
+pub mod stats;
+pub mod suites;
+pub mod topology;
+pub mod units;
+
+pub use harness::{benchmark, black_box, BenchmarkResult};
+pub use options::{BenchmarkOptions, Config};
+pub use registry::{Benchmark, Category, Registry, Requirements, Suite};
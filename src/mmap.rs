@@ -0,0 +1,202 @@
+use failure::{format_err, Error};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::os::unix::io::AsRawFd;
+use std::ptr;
+use std::slice;
+
+// The madvise hint the mmap suites give for their mapping.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Advice {
+    Normal,
+    Sequential,
+    Random,
+    // Starts reading the whole file into the page cache in the background.
+    WillNeed,
+}
+
+impl Advice {
+    pub fn from_name(name: &str) -> Result<Advice, Error> {
+        match name {
+            "normal" => Ok(Advice::Normal),
+            "sequential" => Ok(Advice::Sequential),
+            "random" => Ok(Advice::Random),
+            "willneed" => Ok(Advice::WillNeed),
+            _ => Err(format_err!(
+                "unknown madvise advice {}, expected normal, sequential, random or willneed",
+                name
+            )),
+        }
+    }
+
+    fn flag(self) -> libc::c_int {
+        match self {
+            Advice::Normal => libc::MADV_NORMAL,
+            Advice::Sequential => libc::MADV_SEQUENTIAL,
+            Advice::Random => libc::MADV_RANDOM,
+            Advice::WillNeed => libc::MADV_WILLNEED,
+        }
+    }
+}
+
+impl fmt::Display for Advice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Advice::Normal => "normal",
+            Advice::Sequential => "sequential",
+            Advice::Random => "random",
+            Advice::WillNeed => "willneed",
+        };
+        f.write_str(name)
+    }
+}
+
+// A mapping of a file or of anonymous memory, unmapped when dropped. Writes to a file mapping go to
+// the file through the page cache, and are only durable after `sync`.
+pub struct Mapping {
+    pointer: *mut u8,
+    length: usize,
+}
+
+// The mapping owns its memory, like an `AlignedBuffer`.
+unsafe impl Send for Mapping {}
+
+impl Mapping {
+    // Maps the first `length` bytes of `file`, which has to be opened for reading, and for writing
+    // too if `writable`. `populate` faults all of it in up front with MAP_POPULATE, only on linux.
+    pub fn new(
+        file: &fs::File,
+        length: usize,
+        writable: bool,
+        populate: bool,
+    ) -> Result<Mapping, Error> {
+        let protection = if writable {
+            libc::PROT_READ | libc::PROT_WRITE
+        } else {
+            libc::PROT_READ
+        };
+        let mut flags = libc::MAP_SHARED;
+        #[cfg(target_os = "linux")]
+        {
+            if populate {
+                flags |= libc::MAP_POPULATE;
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = populate;
+
+        let pointer = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                length,
+                protection,
+                flags,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if pointer == libc::MAP_FAILED {
+            return Err(format_err!(
+                "failed to map file: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        Ok(Mapping {
+            pointer: pointer as *mut u8,
+            length,
+        })
+    }
+
+    // Maps `length` bytes of zeroed memory, which isn't backed by anything until it's touched.
+    pub fn anonymous(length: usize) -> Result<Mapping, Error> {
+        let pointer = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                length,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if pointer == libc::MAP_FAILED {
+            return Err(format_err!(
+                "failed to map memory: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        Ok(Mapping {
+            pointer: pointer as *mut u8,
+            length,
+        })
+    }
+
+    pub fn advise(&self, advice: Advice) -> Result<(), Error> {
+        let result = unsafe { libc::madvise(self.pointer as *mut _, self.length, advice.flag()) };
+        if result != 0 {
+            return Err(format_err!(
+                "failed to advise mapping: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        Ok(())
+    }
+
+    // Unmaps the pages from the process without touching the file, so the page cache can drop
+    // them again, see `cache::evict_file`.
+    pub fn drop_pages(&self) -> Result<(), Error> {
+        let result =
+            unsafe { libc::madvise(self.pointer as *mut _, self.length, libc::MADV_DONTNEED) };
+        if result != 0 {
+            return Err(format_err!(
+                "failed to drop mapped pages: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        Ok(())
+    }
+
+    // Writes the pages `length` bytes from `offset` are on back to the file and waits for them to
+    // be durable, like fsync on the range.
+    pub fn sync(&self, offset: usize, length: usize) -> Result<(), Error> {
+        let start = offset - offset % page_size::get();
+        let result = unsafe {
+            libc::msync(
+                self.pointer.add(start) as *mut _,
+                offset + length - start,
+                libc::MS_SYNC,
+            )
+        };
+        if result != 0 {
+            return Err(format_err!(
+                "failed to sync mapping: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Deref for Mapping {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.pointer, self.length) }
+    }
+}
+
+impl DerefMut for Mapping {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.pointer, self.length) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.pointer as *mut _, self.length);
+        }
+    }
+}
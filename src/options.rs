@@ -0,0 +1,441 @@
+use crate::cache::{CacheMode, DROP_CACHES};
+use crate::mmap::Advice;
+use crate::registry::Registry;
+use crate::resources::logical_block_size;
+use crate::units::format_bytes;
+use failure::{format_err, Error};
+use std::env;
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// The size of the smallest file the disk suites read or write in blocks.
+const MAX_BLOCK_SIZE: usize = n_gib_bytes!(1) as usize;
+
+// The pointer chases of `memory_read_working_set` need at least a cache line to chase.
+const MIN_WORKING_SET: usize = 64;
+
+// How long `benchmark` warms up and measures for, and where suites put their files. The
+// measurement stops at whichever comes last of `measure` and `min_iterations`, but never goes past
+// `max_iterations`. The warmup runs for at least one iteration, even with a `warmup` of 0, and
+// doesn't go past `max_iterations` either.
+#[derive(Clone, Debug)]
+pub struct BenchmarkOptions {
+    pub warmup: Duration,
+    pub measure: Duration,
+    pub min_iterations: usize,
+    pub max_iterations: Option<usize>,
+    // Where suites create their files, e.g. a mount of the disk that should be benchmarked.
+    pub directory: PathBuf,
+    pub cache: CacheMode,
+    // Drops the whole page cache before reading a cold file, not only the file's pages.
+    pub drop_caches: bool,
+    // Replaces the block size of the suites that have one.
+    pub block_size: Option<usize>,
+    // Runs the suites that have a block size once for each of these instead.
+    pub block_sizes: Option<Vec<usize>>,
+    // The same for the number of requests suites like the io_uring ones keep in flight.
+    pub queue_depth: Option<usize>,
+    pub queue_depths: Option<Vec<usize>>,
+    // Replaces the madvise hint the mmap suites give for their mapping, and has them map it with
+    // MAP_POPULATE.
+    pub madvise: Option<Advice>,
+    pub populate: bool,
+    // The working set sizes `memory_read_working_set` sweeps, instead of 4 KiB to 4 GiB.
+    pub working_sets: Option<Vec<usize>>,
+    // The buffer sizes the memcpy, memmove and memset suites sweep, instead of every other power
+    // of two from 64 B to 1 GiB.
+    pub copy_sizes: Option<Vec<usize>>,
+}
+
+impl Default for BenchmarkOptions {
+    fn default() -> BenchmarkOptions {
+        BenchmarkOptions {
+            warmup: Duration::from_millis(100),
+            measure: Duration::from_millis(5000),
+            min_iterations: 1,
+            max_iterations: None,
+            directory: env::temp_dir(),
+            cache: CacheMode::Cold,
+            drop_caches: false,
+            block_size: None,
+            block_sizes: None,
+            queue_depth: None,
+            queue_depths: None,
+            madvise: None,
+            populate: false,
+            working_sets: None,
+            copy_sizes: None,
+        }
+    }
+}
+
+impl BenchmarkOptions {
+    pub fn set(&mut self, option: &str, value: &str) -> Result<(), Error> {
+        match option {
+            "warmup" => self.warmup = parse_duration(value)?,
+            "measure" => self.measure = parse_duration(value)?,
+            "min-iterations" => self.min_iterations = value.parse()?,
+            "max-iterations" => self.max_iterations = Some(positive(option, value.parse()?)?),
+            "dir" => self.directory = PathBuf::from(value),
+            "cache" => self.cache = CacheMode::from_name(value)?,
+            "drop-caches" => self.drop_caches = value.parse()?,
+            "block-size" => self.block_size = Some(positive(option, parse_size(value)?)?),
+            "block-sizes" => self.block_sizes = Some(parse_size_range(value)?),
+            "queue-depth" => self.queue_depth = Some(positive(option, value.parse()?)?),
+            "queue-depths" => self.queue_depths = Some(parse_count_range(value)?),
+            "madvise" => self.madvise = Some(Advice::from_name(value)?),
+            "populate" => self.populate = value.parse()?,
+            "working-sets" => {
+                let sizes = parse_size_range(value)?;
+                if sizes[0] < MIN_WORKING_SET {
+                    return Err(format_err!(
+                        "working sets have to hold at least one {} cache line, got {}",
+                        format_bytes(MIN_WORKING_SET as u64),
+                        value
+                    ));
+                }
+                self.working_sets = Some(sizes);
+            }
+            "copy-sizes" => self.copy_sizes = Some(parse_size_range(value)?),
+            _ => return Err(format_err!("unknown benchmark option: {}", option)),
+        }
+        Ok(())
+    }
+
+    // Checks the options that only make sense together, once they're all set: blocks have to fit
+    // in the files the disk suites read and write, and O_DIRECT fails to read blocks that aren't a
+    // multiple of the logical block size of the disk. Dropping the page cache takes root, and a
+    // /proc that isn't read-only, which most containers' is.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.drop_caches {
+            OpenOptions::new()
+                .write(true)
+                .open(DROP_CACHES)
+                .map_err(|e| {
+                    format_err!(
+                        "--drop-caches needs to write {}, usually as root: {}",
+                        DROP_CACHES,
+                        e
+                    )
+                })?;
+        }
+
+        let block_sizes = self
+            .block_size
+            .iter()
+            .chain(self.block_sizes.iter().flatten());
+        let logical_block_size = logical_block_size(&self.directory);
+        for &block_size in block_sizes {
+            if block_size > MAX_BLOCK_SIZE {
+                return Err(format_err!(
+                    "block size {} is larger than the {} files the disk tests use",
+                    format_bytes(block_size as u64),
+                    format_bytes(MAX_BLOCK_SIZE as u64)
+                ));
+            }
+            if self.cache == CacheMode::Direct
+                && !(block_size as u64).is_multiple_of(logical_block_size)
+            {
+                return Err(format_err!(
+                    "block size {} isn't a multiple of the {} logical block size of {}, which \
+                     --cache direct requires",
+                    format_bytes(block_size as u64),
+                    format_bytes(logical_block_size),
+                    self.directory.display()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Every suite gets its own file, so suites never clobber each other's.
+    pub fn scratch_file(&self, suite: &str) -> PathBuf {
+        self.directory.join(format!("napkin-{}.dat", suite))
+    }
+}
+
+// Options given on the command line, plus the ones overridden for specific suites with
+// `SUITE:OPTION=VALUE`, e.g. `disk_read_random:measure=30s`.
+#[derive(Default)]
+pub struct Config {
+    pub defaults: BenchmarkOptions,
+    overrides: Vec<(String, String, String)>,
+}
+
+impl Config {
+    pub fn add_override(&mut self, argument: &str, registry: &Registry) -> Result<(), Error> {
+        let malformed = || format_err!("expected SUITE:OPTION=VALUE, got {}", argument);
+        let colon = argument.find(':').ok_or_else(malformed)?;
+        let equals = argument.find('=').ok_or_else(malformed)?;
+        if equals < colon {
+            return Err(malformed());
+        }
+
+        let (suite, option, value) = (
+            &argument[..colon],
+            &argument[colon + 1..equals],
+            &argument[equals + 1..],
+        );
+        // Catch typos before any suite runs.
+        if registry.get(suite).is_none() {
+            return Err(format_err!(
+                "unknown suite {} in {}, see `list` for all of them",
+                suite,
+                argument
+            ));
+        }
+        self.defaults.clone().set(option, value)?;
+        self.overrides
+            .push((suite.to_string(), option.to_string(), value.to_string()));
+        Ok(())
+    }
+
+    pub fn for_suite(&self, name: &str) -> BenchmarkOptions {
+        let mut options = self.defaults.clone();
+        for (suite, option, value) in &self.overrides {
+            if suite == name {
+                options.set(option, value).unwrap();
+            }
+        }
+        options
+    }
+}
+
+// Parses durations like `200ms`, `5s` or `2m`. A bare number is taken as milliseconds.
+pub fn parse_duration(value: &str) -> Result<Duration, Error> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format_err!("invalid duration: {}", value))?;
+
+    let nanoseconds_per_unit = match unit.trim() {
+        "ns" => 1.0,
+        "us" | "μs" => 1e3,
+        "" | "ms" => 1e6,
+        "s" => 1e9,
+        "m" | "min" => 60e9,
+        _ => return Err(format_err!("invalid duration unit in {}", value)),
+    };
+    Ok(Duration::from_nanos((number * nanoseconds_per_unit) as u64))
+}
+
+// Parses sizes like `512`, `512B`, `4KiB` or `4 MiB`.
+pub fn parse_size(value: &str) -> Result<usize, Error> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: usize = number
+        .parse()
+        .map_err(|_| format_err!("invalid size: {}", value))?;
+
+    let bytes_per_unit = match unit.trim() {
+        "" | "B" => 1,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        _ => {
+            return Err(format_err!(
+                "invalid size unit in {}, expected B, KiB, MiB or GiB",
+                value
+            ))
+        }
+    };
+    number
+        .checked_mul(bytes_per_unit)
+        .ok_or_else(|| format_err!("size too large: {}", value))
+}
+
+// Options that divide by their value or bound a loop with it can't be 0.
+fn positive(option: &str, value: usize) -> Result<usize, Error> {
+    if value == 0 {
+        return Err(format_err!("{} has to be at least 1", option));
+    }
+    Ok(value)
+}
+
+// Parses ranges like `512B..4MiB` into every power of two from the start to the end.
+pub fn parse_size_range(value: &str) -> Result<Vec<usize>, Error> {
+    parse_range(value, "512B..4MiB", parse_size)
+}
+
+// Parses ranges like `1..256` into every power of two from the start to the end.
+pub fn parse_count_range(value: &str) -> Result<Vec<usize>, Error> {
+    parse_range(value, "1..256", |bound| {
+        bound
+            .trim()
+            .parse()
+            .map_err(|_| format_err!("invalid number: {}", bound))
+    })
+}
+
+fn parse_range<F: Fn(&str) -> Result<usize, Error>>(
+    value: &str,
+    example: &str,
+    parse: F,
+) -> Result<Vec<usize>, Error> {
+    let malformed = || {
+        format_err!(
+            "expected a range of powers of two like {}, got {}",
+            example,
+            value
+        )
+    };
+    let mut bounds = value.splitn(2, "..");
+    let start = parse(bounds.next().ok_or_else(malformed)?)?;
+    let end = parse(bounds.next().ok_or_else(malformed)?)?;
+    if !start.is_power_of_two() || start > end {
+        return Err(malformed());
+    }
+
+    // Stops at the largest power of two that fits in a usize if the end is past it.
+    let mut values = vec![];
+    let mut value = Some(start);
+    while let Some(power) = value.filter(|&power| power <= end) {
+        values.push(power);
+        value = power.checked_mul(2);
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("200ms").unwrap(), Duration::from_millis(200));
+        assert_eq!(parse_duration("100").unwrap(), Duration::from_millis(100));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_duration("10us").unwrap(), Duration::from_micros(10));
+        assert_eq!(parse_duration("10μs").unwrap(), Duration::from_micros(10));
+        assert_eq!(parse_duration("50ns").unwrap(), Duration::from_nanos(50));
+        assert!(parse_duration("3h").is_err());
+        assert!(parse_duration("fast").is_err());
+    }
+
+    #[test]
+    fn parse_size_units() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("512B").unwrap(), 512);
+        assert_eq!(parse_size("4KiB").unwrap(), 4096);
+        assert_eq!(parse_size("4 MiB").unwrap(), 4 << 20);
+        assert_eq!(parse_size("1GiB").unwrap(), 1 << 30);
+        assert!(parse_size("4KB").is_err());
+        assert!(parse_size("KiB").is_err());
+    }
+
+    #[test]
+    fn parse_size_range_powers_of_two() {
+        assert_eq!(
+            parse_size_range("512B..4KiB").unwrap(),
+            vec![512, 1024, 2048, 4096]
+        );
+        // The end doesn't have to be a power of two, the last one below it is.
+        assert_eq!(parse_size_range("1KiB..3KiB").unwrap(), vec![1024, 2048]);
+        assert_eq!(parse_size_range("4KiB..4KiB").unwrap(), vec![4096]);
+        assert!(parse_size_range("4KiB..512B").is_err());
+        assert!(parse_size_range("3KiB..8KiB").is_err());
+        assert!(parse_size_range("512B").is_err());
+        assert!(parse_size_range("512B..lots").is_err());
+    }
+
+    #[test]
+    fn parse_count_range_powers_of_two() {
+        assert_eq!(parse_count_range("1..8").unwrap(), vec![1, 2, 4, 8]);
+        assert!(parse_count_range("0..8").is_err());
+    }
+
+    #[test]
+    fn overflowing_sizes_are_rejected() {
+        assert!(parse_size(&format!("{}GiB", usize::MAX)).is_err());
+        assert!(parse_size("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn ranges_stop_before_overflowing() {
+        let values = parse_count_range(&format!("1..{}", usize::MAX)).unwrap();
+        assert_eq!(values.len(), usize::BITS as usize);
+        assert_eq!(*values.last().unwrap(), 1 << (usize::BITS - 1));
+    }
+
+    #[test]
+    fn zero_iterations_and_block_sizes_are_rejected() {
+        let mut options = BenchmarkOptions::default();
+        assert!(options.set("max-iterations", "0").is_err());
+        assert!(options.set("block-size", "0").is_err());
+        assert!(options.set("block-size", "0KiB").is_err());
+        options.set("max-iterations", "1").unwrap();
+        assert_eq!(options.max_iterations, Some(1));
+    }
+
+    #[test]
+    fn blocks_larger_than_the_files_are_rejected() {
+        let mut options = BenchmarkOptions::default();
+        options.set("block-sizes", "1GiB..2GiB").unwrap();
+        assert!(options.validate().is_err());
+        options.set("block-sizes", "512MiB..1GiB").unwrap();
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn working_sets_smaller_than_a_line_are_rejected() {
+        let mut options = BenchmarkOptions::default();
+        assert!(options.set("working-sets", "16B..32B").is_err());
+        assert!(options.set("working-sets", "32B..4KiB").is_err());
+        options.set("working-sets", "64B..128B").unwrap();
+        assert_eq!(options.working_sets, Some(vec![64, 128]));
+    }
+
+    #[test]
+    fn empty_queues_are_rejected() {
+        let mut options = BenchmarkOptions::default();
+        assert!(options.set("queue-depth", "0").is_err());
+        assert!(options.set("queue-depths", "0..4").is_err());
+    }
+
+    #[test]
+    fn overrides_apply_to_their_suite_only() {
+        let registry = crate::suites::builtin();
+        let mut config = Config::default();
+        config
+            .add_override("disk_read_random:measure=30s", &registry)
+            .unwrap();
+        assert_eq!(
+            config.for_suite("disk_read_random").measure,
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            config.for_suite("disk_read_sequential").measure,
+            config.defaults.measure
+        );
+    }
+
+    #[test]
+    fn malformed_overrides_are_rejected() {
+        let registry = crate::suites::builtin();
+        let mut config = Config::default();
+        for argument in &[
+            "measure=30s",
+            "disk_read_random:measure",
+            "a=b:c",
+            "disk_read_random:bogus=1",
+            "disk_read_random:measure=fast",
+            "disk_read_random:max-iterations=0",
+            "disk_raed_random:measure=30s",
+        ] {
+            assert!(
+                config.add_override(argument, &registry).is_err(),
+                "{}",
+                argument
+            );
+        }
+    }
+}
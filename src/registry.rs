@@ -0,0 +1,350 @@
+use crate::harness::{benchmark_with_state, BenchmarkResult};
+use crate::options::BenchmarkOptions;
+use crate::resources::{available_disk_space, available_memory};
+use crate::units::format_bytes;
+use failure::Error;
+use std::fmt;
+use std::net::{SocketAddr, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Category {
+    Memory,
+    Syscall,
+    Disk,
+    Network,
+    ExternalService,
+    Cpu,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Category::Memory => "memory",
+            Category::Syscall => "syscall",
+            Category::Disk => "disk",
+            Category::Network => "network",
+            Category::ExternalService => "external service",
+            Category::Cpu => "cpu",
+        };
+        f.write_str(name)
+    }
+}
+
+// What a benchmark needs from the machine it runs on.
+#[derive(Clone, Default, Debug)]
+pub struct Requirements {
+    pub linux_only: bool,
+    pub avx2: bool,
+    // A service that has to be running for the benchmark to work, e.g. redis on localhost.
+    pub service: Option<Service>,
+    // Bytes of memory and disk space the benchmark's setup allocates and writes.
+    pub memory: u64,
+    pub disk: u64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Service {
+    pub name: &'static str,
+    pub address: &'static str,
+}
+
+impl Service {
+    // Why the service can't be reached, if it can't. Only checks that something accepts
+    // connections on its address, whether that's really the service is up to the benchmark.
+    fn unreachable_reason(&self) -> Option<String> {
+        let address = match self.address.parse::<SocketAddr>() {
+            Ok(address) => address,
+            Err(e) => return Some(format!("{} isn't a valid address: {}", self.address, e)),
+        };
+        TcpStream::connect_timeout(&address, SERVICE_TIMEOUT)
+            .err()
+            .map(|e| format!("{} isn't reachable: {}", self, e))
+    }
+}
+
+impl fmt::Display for Service {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} on {}", self.name, self.address)
+    }
+}
+
+// How long to wait for a service to accept a connection before calling it unreachable.
+const SERVICE_TIMEOUT: Duration = Duration::from_millis(500);
+
+impl Requirements {
+    // Why the benchmark can't run here, if it can't.
+    pub fn unsupported_reason(&self) -> Option<String> {
+        if self.linux_only && !cfg!(target_os = "linux") {
+            return Some(String::from("only supported on linux"));
+        }
+        if self.avx2 && !avx2_supported() {
+            return Some(String::from("requires a cpu with AVX2"));
+        }
+        self.service
+            .and_then(|service| service.unreachable_reason())
+    }
+
+    // Why the benchmark won't fit on this machine right now, if it won't. `scratch` is the
+    // directory the benchmark creates its files in.
+    pub fn insufficient_resources(&self, scratch: &Path) -> Option<String> {
+        if let Some(available) = available_memory() {
+            if self.memory > available {
+                return Some(format!(
+                    "needs {} of memory but only {} is available",
+                    format_bytes(self.memory),
+                    format_bytes(available)
+                ));
+            }
+        }
+        if self.disk > 0 {
+            if let Some(available) = available_disk_space(scratch) {
+                if self.disk > available {
+                    return Some(format!(
+                        "needs {} of disk space in {} but only {} is free",
+                        format_bytes(self.disk),
+                        scratch.display(),
+                        format_bytes(available)
+                    ));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn avx2_supported() -> bool {
+    is_x86_feature_detected!("avx2")
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn avx2_supported() -> bool {
+    false
+}
+
+// A single benchmark: `setup` is called before the warmup and before the measurement, and
+// `iteration` is then called in a loop until the time runs out or it returns false. See
+// `benchmark` for details.
+pub trait Benchmark {
+    type State;
+
+    // Identifier used to select the benchmark on the command line, e.g. `memory_read_random`.
+    fn name(&self) -> &'static str;
+    // What the results are printed as, e.g. `Random Read Vec`.
+    fn title(&self) -> &'static str;
+    // One line on what's being measured, shown by `list`.
+    fn description(&self) -> &'static str;
+    fn category(&self) -> Category;
+    fn tags(&self) -> &'static [&'static str] {
+        &[]
+    }
+    fn requirements(&self) -> Requirements {
+        Requirements::default()
+    }
+    // 0 for benchmarks where throughput doesn't make sense, e.g. syscalls.
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        0
+    }
+    // The block size of benchmarks that read or write in blocks, e.g. the disk suites. Having one
+    // makes the benchmark run once per block size when sweeping, see `BenchmarkOptions`.
+    fn default_block_size(&self) -> Option<usize> {
+        None
+    }
+    fn block_size(&self, options: &BenchmarkOptions) -> usize {
+        options
+            .block_size
+            .or_else(|| self.default_block_size())
+            .unwrap_or(0)
+    }
+    // The same for the number of requests in flight, for benchmarks that submit them
+    // asynchronously.
+    fn default_queue_depth(&self) -> Option<usize> {
+        None
+    }
+    fn queue_depth(&self, options: &BenchmarkOptions) -> usize {
+        options
+            .queue_depth
+            .or_else(|| self.default_queue_depth())
+            .unwrap_or(1)
+    }
+
+    // Options the results depend on, e.g. the cache mode of the disk suites, reported with them.
+    fn parameters(&self, _options: &BenchmarkOptions) -> Vec<(&'static str, String)> {
+        vec![]
+    }
+
+    // Fails when the benchmark can't run after all, e.g. when the service it needs went away, in
+    // which case the suite fails and the run goes on with the next one.
+    fn setup(&self, options: &BenchmarkOptions) -> Result<Self::State, Error>;
+    fn iteration(&self, state: &mut Self::State) -> bool;
+    // Called with the state the measurement ended with, to add what the benchmark measured
+    // itself to the result, e.g. the latency of individual requests.
+    fn finish(&self, _state: Self::State, result: BenchmarkResult) -> BenchmarkResult {
+        result
+    }
+    // Called once after the measurement, e.g. to remove files created by `setup`.
+    fn teardown(&self, _options: &BenchmarkOptions) {}
+}
+
+// Object-safe side of `Benchmark` so benchmarks with different states can be kept together.
+// Implement this directly for suites that produce more than one result.
+pub trait Suite {
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    fn category(&self) -> Category;
+    fn tags(&self) -> &'static [&'static str];
+    fn requirements(&self) -> Requirements;
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize;
+    fn run(&self, options: &BenchmarkOptions) -> Result<Vec<BenchmarkResult>, Error>;
+
+    // Categories count as tags too, so `disk` selects all the disk suites.
+    fn has_tag(&self, tag: &str) -> bool {
+        self.tags().contains(&tag) || self.category().to_string() == tag
+    }
+
+    // What the suite runs once for each of, for suites that sweep something themselves, e.g. `13
+    // sizes from 64 B to 1 GiB`. Shown by `list`, since it depends on the options.
+    fn sweep_description(&self, _options: &BenchmarkOptions) -> Option<String> {
+        None
+    }
+
+    // The longest `run` can take, not counting setup. Benchmarks that run out of work finish
+    // sooner.
+    fn estimated_runtime(&self, options: &BenchmarkOptions) -> Duration {
+        options.warmup + options.measure
+    }
+}
+
+impl<B: Benchmark> Suite for B {
+    fn name(&self) -> &'static str {
+        Benchmark::name(self)
+    }
+
+    fn description(&self) -> &'static str {
+        Benchmark::description(self)
+    }
+
+    fn category(&self) -> Category {
+        Benchmark::category(self)
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        Benchmark::tags(self)
+    }
+
+    fn requirements(&self) -> Requirements {
+        Benchmark::requirements(self)
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        Benchmark::bytes_per_iteration(self, options)
+    }
+
+    fn run(&self, options: &BenchmarkOptions) -> Result<Vec<BenchmarkResult>, Error> {
+        let mut results = vec![];
+        for options in sweep(self, options) {
+            let measurement = benchmark_with_state(
+                &options,
+                || self.setup(&options),
+                |state| self.iteration(state),
+            );
+            let result = measurement.map(|(result, state)| self.finish(state, result));
+            if result.is_err() {
+                self.teardown(&options);
+            }
+
+            let mut result = result?.labelled(
+                Benchmark::title(self),
+                Benchmark::bytes_per_iteration(self, &options),
+            );
+            if let Some(block_size) = options.block_size {
+                result = result.with_parameter("block_size", &block_size.to_string());
+            }
+            if let Some(queue_depth) = options.queue_depth {
+                result = result.with_parameter("queue_depth", &queue_depth.to_string());
+            }
+            for (name, value) in self.parameters(&options) {
+                result = result.with_parameter(name, &value);
+            }
+            results.push(result);
+        }
+        // Once the sweep is done, so runs with the same file, e.g. every block size of
+        // `disk_read_random`, reuse it instead of writing it again.
+        self.teardown(options);
+        Ok(results)
+    }
+
+    fn estimated_runtime(&self, options: &BenchmarkOptions) -> Duration {
+        (options.warmup + options.measure) * sweep(self, options).len() as u32
+    }
+}
+
+// The options for every run of the benchmark: one per block size and queue depth when sweeping
+// those and the benchmark has them, otherwise just the one.
+fn sweep<B: Benchmark>(benchmark: &B, options: &BenchmarkOptions) -> Vec<BenchmarkOptions> {
+    let block_sizes = axis(
+        benchmark.default_block_size(),
+        options.block_size,
+        &options.block_sizes,
+    );
+    let queue_depths = axis(
+        benchmark.default_queue_depth(),
+        options.queue_depth,
+        &options.queue_depths,
+    );
+
+    let mut runs = vec![];
+    for block_size in &block_sizes {
+        for queue_depth in &queue_depths {
+            runs.push(BenchmarkOptions {
+                block_size: *block_size,
+                queue_depth: *queue_depth,
+                ..options.clone()
+            });
+        }
+    }
+    runs
+}
+
+// The values of one option to run a benchmark with, `None` if the benchmark doesn't have it.
+fn axis(
+    default: Option<usize>,
+    value: Option<usize>,
+    sweep: &Option<Vec<usize>>,
+) -> Vec<Option<usize>> {
+    match (default, sweep) {
+        (None, _) => vec![None],
+        (Some(_), Some(values)) => values.iter().cloned().map(Some).collect(),
+        (Some(default), None) => vec![Some(value.unwrap_or(default))],
+    }
+}
+
+#[derive(Default)]
+pub struct Registry {
+    suites: Vec<Box<dyn Suite>>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    pub fn register<S: Suite + 'static>(&mut self, suite: S) -> &mut Registry {
+        self.suites.push(Box::new(suite));
+        self
+    }
+
+    pub fn suites(&self) -> impl Iterator<Item = &dyn Suite> {
+        self.suites.iter().map(|suite| suite.as_ref())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Suite> {
+        self.suites().find(|suite| suite.name() == name)
+    }
+
+    pub fn tagged<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a dyn Suite> {
+        self.suites().filter(move |suite| suite.has_tag(tag))
+    }
+}
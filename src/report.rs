@@ -0,0 +1,369 @@
+use crate::harness::BenchmarkResult;
+use crate::stats::Statistics;
+use crate::units::{
+    format_metric, format_nanoseconds, metric_label, napkin_duration, napkin_throughput,
+};
+use byte_unit::Byte;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::io::prelude::*;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Human,
+    Json,
+    Ndjson,
+    Markdown,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Format {
+        match name {
+            "json" => Format::Json,
+            "ndjson" => Format::Ndjson,
+            _ => Format::Human,
+        }
+    }
+}
+
+// Everything `print_results` shows, but as numbers rather than text. Times are in nanoseconds and
+// the byte-derived fields are only present when the suite handles bytes.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BenchmarkRecord {
+    // The suite the result came from, e.g. `memory_read_random`, and the result's title. Missing in
+    // baselines saved before records had it.
+    #[serde(default)]
+    pub suite: String,
+    pub name: String,
+    pub iterations: usize,
+    pub duration_ns: u64,
+    pub cycles: u64,
+    pub bytes_per_iteration: usize,
+    pub iterations_per_second: f64,
+    pub ns_per_iteration: f64,
+    pub cycles_per_iteration: f64,
+    pub throughput_bytes_per_second: Option<f64>,
+    pub time_per_mib_ns: Option<f64>,
+    pub time_per_gib_ns: Option<f64>,
+    pub time_per_tib_ns: Option<f64>,
+    pub statistics: Statistics,
+    // Missing in baselines saved before results had parameters and metrics.
+    #[serde(default)]
+    pub parameters: BTreeMap<String, String>,
+    #[serde(default)]
+    pub metrics: BTreeMap<String, f64>,
+}
+
+impl BenchmarkRecord {
+    pub fn new(suite: &str, result: &BenchmarkResult) -> BenchmarkRecord {
+        let bytes_per_iteration = result.bytes_per_iteration;
+        let time_per = |bytes: u128| {
+            if bytes_per_iteration > 0 {
+                Some(result.nanoseconds_per_byte(bytes_per_iteration) * bytes as f64)
+            } else {
+                None
+            }
+        };
+
+        BenchmarkRecord {
+            suite: suite.to_string(),
+            name: result.name.clone(),
+            iterations: result.iterations,
+            duration_ns: result.duration.as_nanos() as u64,
+            cycles: result.cycles,
+            bytes_per_iteration,
+            iterations_per_second: result.iterations_per_second(),
+            ns_per_iteration: result.nanoseconds_per_iteration(),
+            cycles_per_iteration: result.cycles as f64 / result.iterations as f64,
+            throughput_bytes_per_second: if bytes_per_iteration > 0 {
+                Some(result.iterations_per_second() * bytes_per_iteration as f64)
+            } else {
+                None
+            },
+            time_per_mib_ns: time_per(n_mib_bytes!(1)),
+            time_per_gib_ns: time_per(n_gib_bytes!(1)),
+            time_per_tib_ns: time_per(n_tib_bytes!(1)),
+            statistics: result.statistics.clone(),
+            parameters: result.parameters.iter().cloned().collect(),
+            metrics: result.metrics.iter().cloned().collect(),
+        }
+    }
+}
+
+// Suites hand their results to the reporter instead of printing them, so the same run can be
+// rendered for humans or streamed to other programs.
+pub struct Reporter {
+    format: Format,
+    records: Vec<BenchmarkRecord>,
+}
+
+impl Reporter {
+    pub fn new(format: Format) -> Reporter {
+        Reporter {
+            format,
+            records: Vec::new(),
+        }
+    }
+
+    pub fn report(&mut self, suite: &str, result: &BenchmarkResult) {
+        let record = BenchmarkRecord::new(suite, result);
+        match self.format {
+            Format::Human => result.print_results(),
+            Format::Ndjson => {
+                let stdout = io::stdout();
+                let mut stdout = stdout.lock();
+                serde_json::to_writer(&mut stdout, &record).unwrap();
+                writeln!(stdout).unwrap();
+                stdout.flush().unwrap();
+            }
+            Format::Json | Format::Markdown => {}
+        }
+        self.records.push(record);
+    }
+
+    // Sums up the results of a suite that ran once per e.g. block size as a table, one row per
+    // run, so the curve is easy to see.
+    pub fn report_sweep(&self, results: &[BenchmarkResult]) {
+        if self.format != Format::Human || results.len() < 2 {
+            return;
+        }
+
+        let mut header: Vec<String> = results[0]
+            .parameters
+            .iter()
+            .map(|(name, _)| name.clone())
+            .filter(|name| {
+                results
+                    .iter()
+                    .any(|result| parameter(result, name) != parameter(&results[0], name))
+            })
+            .collect();
+        let swept = header.len();
+        header.extend(
+            ["Latency", "p99", "Throughput"]
+                .iter()
+                .map(|column| column.to_string()),
+        );
+        header.extend(
+            results[0]
+                .metrics
+                .iter()
+                .map(|(name, _)| metric_label(name).to_string()),
+        );
+
+        let mut rows = vec![header];
+        for result in results {
+            let mut row: Vec<String> = rows[0][..swept]
+                .iter()
+                .map(|name| parameter(result, name).unwrap_or("").to_string())
+                .collect();
+            row.push(format_nanoseconds(result.nanoseconds_per_iteration()));
+            row.push(format_nanoseconds(result.statistics.p99));
+            row.push(if result.bytes_per_iteration > 0 {
+                format!(
+                    "{}/s",
+                    Byte::from_bytes(
+                        (result.iterations_per_second() * result.bytes_per_iteration as f64)
+                            as u128
+                    )
+                    .get_appropriate_unit(true)
+                    .format(2)
+                )
+            } else {
+                String::from("N/A")
+            });
+            row.extend(
+                result
+                    .metrics
+                    .iter()
+                    .map(|(name, value)| format_metric(name, *value)),
+            );
+            rows.push(row);
+        }
+
+        println!("\n[{}] Sweep:", results[0].name);
+        print!("{}", table(&rows));
+    }
+
+    pub fn records(&self) -> &[BenchmarkRecord] {
+        &self.records
+    }
+
+    // Progress and diagnostics go to stderr in the machine-readable formats so stdout stays
+    // parseable.
+    pub fn log(&self, message: &str) {
+        match self.format {
+            Format::Human => println!("{}", message),
+            Format::Json | Format::Ndjson | Format::Markdown => eprintln!("{}", message),
+        }
+    }
+
+    pub fn finish(&mut self) {
+        match self.format {
+            Format::Json => println!("{}", serde_json::to_string_pretty(&self.records).unwrap()),
+            Format::Markdown => print!("{}", markdown_table(&self.records)),
+            Format::Human | Format::Ndjson => {}
+        }
+    }
+}
+
+// The rows of the "Numbers" table in the README, in order, and the suite measuring each along with
+// the parameter that picks its result, for suites with more than one. The rest aren't measured
+// here, and keep the numbers the README has for them.
+type MeasuredBy = Option<(&'static str, Option<(&'static str, &'static str)>)>;
+const README_ROWS: &[(&str, MeasuredBy, [&str; 4])] = &[
+    (
+        "Sequential Memory R/W (64 bytes)",
+        Some(("memory_read_sequential", None)),
+        ["?"; 4],
+    ),
+    (
+        "Random Memory R/W (64 bytes)",
+        Some(("memory_read_latency", Some(("loads", "dependent")))),
+        ["?"; 4],
+    ),
+    ("System Call", Some(("syscall_getpid", None)), ["?"; 4]),
+    (
+        "Sequential SSD Read (8 KiB)",
+        Some(("disk_read_sequential", None)),
+        ["?"; 4],
+    ),
+    (
+        "Context Switch `[1] [2]`",
+        None,
+        ["10 μs", "N/A", "N/A", "N/A"],
+    ),
+    (
+        "Sequential SSD write, -fsync (8KiB)",
+        Some(("disk_write_sequential_no_fsync", None)),
+        ["?"; 4],
+    ),
+    (
+        "TCP Echo (TCP overhead) (64 bytes)",
+        Some(("tcp_read_write", None)),
+        ["?"; 4],
+    ),
+    ("Sorting (64-bit integers)", Some(("sort", None)), ["?"; 4]),
+    (
+        "Random SSD Seek (8 KiB)",
+        Some(("disk_read_random", None)),
+        ["?"; 4],
+    ),
+    (
+        "Cloud us-east1 to us-east2",
+        None,
+        ["250 μs", "?", "?", "?"],
+    ),
+    (
+        "Sequential SSD write, +fsync (8KiB)",
+        Some(("disk_write_sequential_fsync", None)),
+        ["?"; 4],
+    ),
+    ("Mutex Lock/Unlock", None, ["?"; 4]),
+    ("{Snappy, Gzip, ..} Compression (? KiB)", None, ["?"; 4]),
+    ("Hashing (? bytes)", None, ["?"; 4]),
+    (
+        "{MySQL, Memcached, Redis, ..} Query",
+        Some(("redis_read_single_key", None)),
+        ["?"; 4],
+    ),
+    ("Envoy/Nginx Overhead", None, ["?"; 4]),
+    ("{JSON, Protobuf, ..} Serializee (?)", None, ["?"; 4]),
+    ("Cloud us-east to us-central", None, ["?"; 4]),
+];
+
+// Whether a suite measures one of the rows of the README's table, which `report` runs.
+pub fn in_readme_table(suite: &str) -> bool {
+    README_ROWS
+        .iter()
+        .any(|(_, measured_by, _)| measured_by.is_some_and(|(name, _)| name == suite))
+}
+
+// The README's "Numbers" table with the numbers of this run, so it can be pasted straight into it.
+// Rows whose suite didn't run, or failed, are left as `?`. A suite that ran more than once, e.g.
+// for every block size, fills its row with its first result.
+fn markdown_table(records: &[BenchmarkRecord]) -> String {
+    let mut rows = vec![vec![
+        String::from("Operation"),
+        String::from("Latency"),
+        String::from("Throughput"),
+        String::from("1 MiB"),
+        String::from("1 GiB"),
+    ]];
+
+    for (operation, measured_by, unmeasured) in README_ROWS {
+        let record = measured_by.and_then(|(suite, parameter)| {
+            records.iter().find(|record| {
+                record.suite == suite
+                    && parameter.is_none_or(|(name, value)| {
+                        record.parameters.get(name).map(String::as_str) == Some(value)
+                    })
+            })
+        });
+
+        let mut row = vec![operation.to_string()];
+        match record {
+            Some(record) => {
+                let not_applicable = || String::from("N/A");
+                row.push(napkin_duration(record.ns_per_iteration));
+                row.push(
+                    record
+                        .throughput_bytes_per_second
+                        .map_or_else(not_applicable, napkin_throughput),
+                );
+                row.push(
+                    record
+                        .time_per_mib_ns
+                        .map_or_else(not_applicable, napkin_duration),
+                );
+                row.push(
+                    record
+                        .time_per_gib_ns
+                        .map_or_else(not_applicable, napkin_duration),
+                );
+            }
+            None => row.extend(unmeasured.iter().map(|cell| cell.to_string())),
+        }
+        rows.push(row);
+    }
+
+    table(&rows)
+}
+
+// Renders rows of cells as a markdown table, with the first row as the header and the columns
+// padded to line up.
+fn table(rows: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap()
+        })
+        .collect();
+
+    let mut table = String::new();
+    for (i, row) in rows.iter().enumerate() {
+        for (cell, width) in row.iter().zip(&widths) {
+            table.push_str(&format!("| {:width$} ", cell, width = width));
+        }
+        table.push_str("|\n");
+
+        if i == 0 {
+            for width in &widths {
+                table.push_str(&format!("|{}", "-".repeat(width + 2)));
+            }
+            table.push_str("|\n");
+        }
+    }
+    table
+}
+
+fn parameter<'a>(result: &'a BenchmarkResult, name: &str) -> Option<&'a str> {
+    result
+        .parameters
+        .iter()
+        .find(|(parameter, _)| parameter == name)
+        .map(|(_, value)| value.as_str())
+}
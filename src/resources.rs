@@ -0,0 +1,127 @@
+use std::ffi::CString;
+use std::fs;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+// Memory that can be allocated without swapping, in bytes. `None` where /proc/meminfo doesn't
+// exist, e.g. on macOS, or on kernels older than 3.14 that don't report `MemAvailable`.
+pub fn available_memory() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|kilobytes| kilobytes.parse::<u64>().ok())
+        .map(|kilobytes| kilobytes * 1024)
+}
+
+// Space that unprivileged users can still write on the filesystem `path` is on, in bytes.
+pub fn available_disk_space(path: &Path) -> Option<u64> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    unsafe {
+        let mut stat: libc::statvfs = mem::zeroed();
+        if libc::statvfs(path.as_ptr(), &mut stat) != 0 {
+            return None;
+        }
+        Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+    }
+}
+
+// The name of the filesystem `path` is on if it's kept in memory, in which case the disk suites
+// measure memory rather than a disk. overlayfs counts too, since containers commonly back it with
+// tmpfs.
+#[cfg(target_os = "linux")]
+pub fn memory_backed_filesystem(path: &Path) -> Option<&'static str> {
+    const TMPFS_MAGIC: i64 = 0x0102_1994;
+    const RAMFS_MAGIC: i64 = 0x8584_58f6;
+    const OVERLAYFS_SUPER_MAGIC: i64 = 0x794c_7630;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let filesystem_type = unsafe {
+        let mut stat: libc::statfs = mem::zeroed();
+        if libc::statfs(path.as_ptr(), &mut stat) != 0 {
+            return None;
+        }
+        stat.f_type as i64
+    };
+
+    match filesystem_type {
+        TMPFS_MAGIC => Some("tmpfs"),
+        RAMFS_MAGIC => Some("ramfs"),
+        OVERLAYFS_SUPER_MAGIC => Some("overlayfs"),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn memory_backed_filesystem(_path: &Path) -> Option<&'static str> {
+    None
+}
+
+// The smallest block O_DIRECT can read from the disk `path` is on, from the device's queue in /sys
+// (its parent's for a partition). 512 bytes, the smallest any disk has, where that isn't known,
+// e.g. on overlayfs or macOS.
+pub fn logical_block_size(path: &Path) -> u64 {
+    const SMALLEST_LOGICAL_BLOCK_SIZE: u64 = 512;
+
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Ok(metadata) = fs::metadata(path) {
+            let device = unsafe {
+                format!(
+                    "/sys/dev/block/{}:{}",
+                    libc::major(metadata.dev()),
+                    libc::minor(metadata.dev())
+                )
+            };
+            let size = ["queue", "../queue"].iter().find_map(|queue| {
+                fs::read_to_string(format!("{}/{}/logical_block_size", device, queue))
+                    .ok()?
+                    .trim()
+                    .parse()
+                    .ok()
+            });
+            if let Some(size) = size {
+                return size;
+            }
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = path;
+    SMALLEST_LOGICAL_BLOCK_SIZE
+}
+
+// The data caches of the first cpu, smallest first, e.g. `("L1", 32768)`. Empty where the kernel
+// doesn't list them in /sys, e.g. on macOS, where `sysctl -a | grep cache` has them instead.
+pub fn cache_sizes() -> Vec<(String, u64)> {
+    let directories = match fs::read_dir("/sys/devices/system/cpu/cpu0/cache") {
+        Ok(directories) => directories,
+        Err(_) => return vec![],
+    };
+
+    let mut caches: Vec<(u64, u64)> = directories
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let read = |name: &str| fs::read_to_string(path.join(name)).ok();
+            if read("type")?.trim() == "Instruction" {
+                return None;
+            }
+            let level = read("level")?.trim().parse().ok()?;
+            let size = read("size")?;
+            let size = size.trim();
+            let bytes = match size.chars().last()? {
+                'K' => size[..size.len() - 1].parse::<u64>().ok()? * 1024,
+                'M' => size[..size.len() - 1].parse::<u64>().ok()? * 1024 * 1024,
+                _ => size.parse().ok()?,
+            };
+            Some((level, bytes))
+        })
+        .collect();
+    caches.sort();
+    caches
+        .into_iter()
+        .map(|(level, bytes)| (format!("L{}", level), bytes))
+        .collect()
+}
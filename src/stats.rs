@@ -0,0 +1,355 @@
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+const BOOTSTRAP_RESAMPLES: usize = 10_000;
+const CONFIDENCE_LEVEL: f64 = 0.95;
+
+// Summary of the per-iteration time of every sample (batch) in a run, in nanoseconds.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Statistics {
+    pub samples: usize,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+    // Bootstrapped confidence interval of the mean at `CONFIDENCE_LEVEL`.
+    pub confidence_interval: (f64, f64),
+    pub outliers: Outliers,
+}
+
+// Samples outside of Tukey's fences, the same classification criterion.rs uses: mild outliers are
+// more than 1.5 IQR outside the quartiles, severe ones more than 3 IQR.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Outliers {
+    pub low_severe: usize,
+    pub low_mild: usize,
+    pub high_mild: usize,
+    pub high_severe: usize,
+}
+
+impl Outliers {
+    pub fn total(&self) -> usize {
+        self.low_severe + self.low_mild + self.high_mild + self.high_severe
+    }
+}
+
+impl Statistics {
+    pub fn new(samples: &[f64]) -> Statistics {
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let mean = mean(&sorted);
+        Statistics {
+            samples: sorted.len(),
+            mean,
+            std_dev: std_dev(&sorted, mean),
+            min: percentile(&sorted, 0.0),
+            median: percentile(&sorted, 50.0),
+            p90: percentile(&sorted, 90.0),
+            p99: percentile(&sorted, 99.0),
+            max: percentile(&sorted, 100.0),
+            confidence_interval: bootstrap_mean(&sorted),
+            outliers: classify_outliers(&sorted),
+        }
+    }
+}
+
+fn mean(samples: &[f64]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+// Sample standard deviation, i.e. with Bessel's correction.
+fn std_dev(samples: &[f64], mean: f64) -> f64 {
+    if samples.len() < 2 {
+        return 0.0;
+    }
+    let sum_of_squares: f64 = samples.iter().map(|x| (x - mean).powi(2)).sum();
+    (sum_of_squares / (samples.len() - 1) as f64).sqrt()
+}
+
+// Linear interpolation between the closest ranks, `sorted` must be sorted.
+pub fn percentile(sorted: &[f64], percentile: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = percentile / 100.0 * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+fn bootstrap_mean(samples: &[f64]) -> (f64, f64) {
+    if samples.len() < 2 {
+        let mean = mean(samples);
+        return (mean, mean);
+    }
+
+    let mut rng = SmallRng::from_entropy();
+    let mut means: Vec<f64> = (0..BOOTSTRAP_RESAMPLES)
+        .map(|_| {
+            let sum: f64 = (0..samples.len())
+                .map(|_| samples[rng.gen_range(0, samples.len())])
+                .sum();
+            sum / samples.len() as f64
+        })
+        .collect();
+    means.sort_by(|a, b| a.total_cmp(b));
+
+    let tail = (1.0 - CONFIDENCE_LEVEL) / 2.0 * 100.0;
+    (percentile(&means, tail), percentile(&means, 100.0 - tail))
+}
+
+fn classify_outliers(sorted: &[f64]) -> Outliers {
+    let mut outliers = Outliers::default();
+    let q1 = percentile(sorted, 25.0);
+    let q3 = percentile(sorted, 75.0);
+    let iqr = q3 - q1;
+
+    for &sample in sorted {
+        if sample < q1 - 3.0 * iqr {
+            outliers.low_severe += 1;
+        } else if sample < q1 - 1.5 * iqr {
+            outliers.low_mild += 1;
+        } else if sample > q3 + 3.0 * iqr {
+            outliers.high_severe += 1;
+        } else if sample > q3 + 1.5 * iqr {
+            outliers.high_mild += 1;
+        }
+    }
+    outliers
+}
+
+// Two-sided p-value of Welch's t-test for the means of two runs being different. Returns `None`
+// when there aren't enough samples, or no variance, to say anything.
+pub fn welch_t_test(a: &Statistics, b: &Statistics) -> Option<f64> {
+    if a.samples < 2 || b.samples < 2 {
+        return None;
+    }
+
+    let variance_a = a.std_dev.powi(2) / a.samples as f64;
+    let variance_b = b.std_dev.powi(2) / b.samples as f64;
+    let variance = variance_a + variance_b;
+    if variance == 0.0 {
+        return None;
+    }
+
+    let t = (a.mean - b.mean) / variance.sqrt();
+    let degrees_of_freedom = variance.powi(2)
+        / (variance_a.powi(2) / (a.samples - 1) as f64
+            + variance_b.powi(2) / (b.samples - 1) as f64);
+
+    Some(incomplete_beta(
+        degrees_of_freedom / (degrees_of_freedom + t * t),
+        degrees_of_freedom / 2.0,
+        0.5,
+    ))
+}
+
+// Lanczos approximation, see Numerical Recipes 6.1.
+fn ln_gamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    if x < 0.5 {
+        // Reflection formula, the approximation is only good for x >= 0.5.
+        return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+
+    let x = x - 1.0;
+    let t = x + G + 0.5;
+    let sum = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .skip(1)
+        .fold(COEFFICIENTS[0], |sum, (i, c)| sum + c / (x + i as f64));
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+// Regularized incomplete beta function I_x(a, b), see Numerical Recipes 6.4.
+fn incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(x, a, b) / a
+    } else {
+        1.0 - front * beta_continued_fraction(1.0 - x, b, a) / b
+    }
+}
+
+// Modified Lentz's method for the continued fraction of the incomplete beta function.
+fn beta_continued_fraction(x: f64, a: f64, b: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 300;
+    const EPSILON: f64 = 1e-14;
+    const TINY: f64 = 1e-300;
+    let avoid_zero = |value: f64| if value.abs() < TINY { TINY } else { value };
+
+    let mut c = 1.0;
+    let mut d = 1.0 / avoid_zero(1.0 - (a + b) * x / (a + 1.0));
+    let mut h = d;
+
+    for m in 1..=MAX_ITERATIONS {
+        let m = m as f64;
+
+        let even = m * (b - m) * x / ((a - 1.0 + 2.0 * m) * (a + 2.0 * m));
+        d = 1.0 / avoid_zero(1.0 + even * d);
+        c = avoid_zero(1.0 + even / c);
+        h *= d * c;
+
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 1.0 + 2.0 * m));
+        d = 1.0 / avoid_zero(1.0 + odd * d);
+        c = avoid_zero(1.0 + odd / c);
+        let delta = d * c;
+        h *= delta;
+
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_interpolates_between_ranks() {
+        let sorted = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+        assert_eq!(percentile(&sorted, 25.0), 1.75);
+        assert_eq!(percentile(&sorted, 50.0), 2.5);
+        assert_eq!(percentile(&sorted, 100.0), 4.0);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+    }
+
+    #[test]
+    fn bootstrap_mean_brackets_the_mean() {
+        assert_eq!(bootstrap_mean(&[5.0; 10]), (5.0, 5.0));
+        assert_eq!(bootstrap_mean(&[5.0]), (5.0, 5.0));
+
+        let samples: Vec<f64> = (1..=100).map(|x| x as f64).collect();
+        let (low, high) = bootstrap_mean(&samples);
+        assert!(low < 50.5 && 50.5 < high, "({}, {})", low, high);
+        // The standard error is about 2.9, so the interval is about 11 wide.
+        assert!(high - low < 20.0, "({}, {})", low, high);
+    }
+
+    #[test]
+    fn classify_outliers_uses_tukeys_fences() {
+        let mut sorted: Vec<f64> = (1..=10).map(|x| x as f64).collect();
+        sorted.extend_from_slice(&[20.0, 100.0]);
+        // The quartiles are 3.75 and 9.25, so the fences are at 17.5 and 25.75 above.
+        let outliers = classify_outliers(&sorted);
+        assert_eq!(outliers.high_mild, 1);
+        assert_eq!(outliers.high_severe, 1);
+        assert_eq!(outliers.low_mild + outliers.low_severe, 0);
+        assert_eq!(outliers.total(), 2);
+    }
+
+    #[test]
+    fn statistics_summarize_unsorted_samples() {
+        let statistics = Statistics::new(&[3.0, 1.0, 2.0]);
+        assert_eq!(statistics.samples, 3);
+        assert_eq!(statistics.mean, 2.0);
+        assert_eq!(statistics.std_dev, 1.0);
+        assert_eq!(statistics.min, 1.0);
+        assert_eq!(statistics.median, 2.0);
+        assert_eq!(statistics.max, 3.0);
+    }
+
+    #[test]
+    fn statistics_sort_nan_samples_last() {
+        // What a batch that ran no iterations divides out to.
+        let statistics = Statistics::new(&[2.0, f64::NAN, 1.0]);
+        assert_eq!(statistics.min, 1.0);
+        assert!(statistics.max.is_nan());
+    }
+
+    fn summary(mean: f64, std_dev: f64, samples: usize) -> Statistics {
+        Statistics {
+            samples,
+            mean,
+            std_dev,
+            min: mean,
+            median: mean,
+            p90: mean,
+            p99: mean,
+            max: mean,
+            confidence_interval: (mean, mean),
+            outliers: Outliers::default(),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn welch_t_test_matches_reference_p_values() {
+        // Reference values from an arbitrary precision implementation.
+        // Equal variances: t = -2.236, 18 degrees of freedom.
+        let p = welch_t_test(&summary(20.0, 5.0, 10), &summary(25.0, 5.0, 10)).unwrap();
+        assert_close(p, 0.038_249_614_516_113_875, 1e-6);
+
+        // Unequal variances and sizes: t = -1.240, 20.45 degrees of freedom.
+        let p = welch_t_test(&summary(10.0, 2.0, 5), &summary(12.0, 6.0, 20)).unwrap();
+        assert_close(p, 0.228_888_593_035_090_24, 1e-6);
+
+        // The test is symmetric.
+        let q = welch_t_test(&summary(12.0, 6.0, 20), &summary(10.0, 2.0, 5)).unwrap();
+        assert_close(p, q, 1e-12);
+    }
+
+    #[test]
+    fn welch_t_test_needs_samples_and_variance() {
+        assert!(welch_t_test(&summary(20.0, 5.0, 1), &summary(25.0, 5.0, 10)).is_none());
+        assert!(welch_t_test(&summary(20.0, 0.0, 10), &summary(25.0, 0.0, 10)).is_none());
+    }
+
+    #[test]
+    fn ln_gamma_known_values() {
+        // ln(sqrt(pi)), ln(9!) and ln(Γ(0.1)).
+        assert_close(ln_gamma(0.5), 0.572_364_942_924_7, 1e-10);
+        assert_close(ln_gamma(10.0), 12.801_827_480_081_5, 1e-10);
+        assert_close(ln_gamma(0.1), 2.252_712_651_734_21, 1e-10);
+        assert_close(ln_gamma(1.0), 0.0, 1e-10);
+    }
+
+    #[test]
+    fn incomplete_beta_known_values() {
+        assert_close(incomplete_beta(0.4, 2.0, 3.0), 0.5248, 1e-10);
+        assert_close(incomplete_beta(0.5, 3.0, 3.0), 0.5, 1e-10);
+        assert_eq!(incomplete_beta(0.0, 2.0, 3.0), 0.0);
+        assert_eq!(incomplete_beta(1.0, 2.0, 3.0), 1.0);
+    }
+}
@@ -0,0 +1,258 @@
+use crate::harness::{benchmark, black_box, BenchmarkResult};
+use crate::options::BenchmarkOptions;
+use crate::registry::{Category, Requirements, Suite};
+use crate::topology::{available_cpus, pin_to_cpu};
+use failure::{format_err, Error};
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::Duration;
+
+// Elements of each of the three arrays, split between the threads. Large enough that they're far
+// out of any cache, like STREAM asks for.
+const STREAM_ARRAY_SIZE: usize = n_mib_bytes!(256) as usize / mem::size_of::<f64>();
+const STREAM_SCALAR: f64 = 3.0;
+
+// The STREAM kernels, plus plain reads and writes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Kernel {
+    // sum += a
+    Read,
+    // a = s
+    Write,
+    // c = a
+    Copy,
+    // b = s * c
+    Scale,
+    // c = a + b
+    Add,
+    // a = b + s * c
+    Triad,
+}
+
+const KERNELS: &[Kernel] = &[
+    Kernel::Read,
+    Kernel::Write,
+    Kernel::Copy,
+    Kernel::Scale,
+    Kernel::Add,
+    Kernel::Triad,
+];
+
+impl Kernel {
+    fn name(self) -> &'static str {
+        match self {
+            Kernel::Read => "read",
+            Kernel::Write => "write",
+            Kernel::Copy => "copy",
+            Kernel::Scale => "scale",
+            Kernel::Add => "add",
+            Kernel::Triad => "triad",
+        }
+    }
+
+    // Arrays read or written per pass, which is what STREAM counts as the bytes moved.
+    fn arrays(self) -> usize {
+        match self {
+            Kernel::Read | Kernel::Write => 1,
+            Kernel::Copy | Kernel::Scale => 2,
+            Kernel::Add | Kernel::Triad => 3,
+        }
+    }
+
+    fn run(self, a: &mut [f64], b: &mut [f64], c: &mut [f64]) {
+        match self {
+            Kernel::Read => {
+                // Independent sums, otherwise every addition waits for the one before it and the
+                // adds are slower than the memory.
+                let mut sums = [0.0; 8];
+                for chunk in a.chunks_exact(8) {
+                    for (sum, x) in sums.iter_mut().zip(chunk) {
+                        *sum += x;
+                    }
+                }
+                black_box(sums);
+            }
+            // The arrays written are passed through `black_box` too, so the stores can't be
+            // eliminated.
+            Kernel::Write => {
+                a.iter_mut().for_each(|x| *x = STREAM_SCALAR);
+                black_box(a);
+            }
+            Kernel::Copy => {
+                c.copy_from_slice(a);
+                black_box(c);
+            }
+            Kernel::Scale => {
+                for (b, c) in b.iter_mut().zip(c.iter()) {
+                    *b = STREAM_SCALAR * c;
+                }
+                black_box(b);
+            }
+            Kernel::Add => {
+                for ((c, a), b) in c.iter_mut().zip(a.iter()).zip(b.iter()) {
+                    *c = a + b;
+                }
+                black_box(c);
+            }
+            Kernel::Triad => {
+                for ((a, b), c) in a.iter_mut().zip(b.iter()).zip(c.iter()) {
+                    *a = b + STREAM_SCALAR * c;
+                }
+                black_box(a);
+            }
+        }
+    }
+}
+
+// Worker threads pinned to a cpu each, every one with its own slice of the arrays. An iteration
+// releases all of them for a pass of the kernel over their slice, and waits for the slowest.
+pub struct MemoryBandwidthTest {
+    barrier: Arc<Barrier>,
+    stop: Arc<AtomicBool>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+fn memory_bandwidth_setup(kernel: Kernel, cpus: &[usize]) -> MemoryBandwidthTest {
+    let barrier = Arc::new(Barrier::new(cpus.len() + 1));
+    let stop = Arc::new(AtomicBool::new(false));
+    let elements = STREAM_ARRAY_SIZE / cpus.len();
+
+    let threads = cpus
+        .iter()
+        .map(|&cpu| {
+            let (barrier, stop) = (barrier.clone(), stop.clone());
+            thread::spawn(move || {
+                // Checked by `check_pinning` already, and the other threads would wait for this
+                // one at the barrier forever if it panicked.
+                if let Err(e) = pin_to_cpu(cpu) {
+                    eprintln!("Running unpinned, {}", e);
+                }
+                // Written by the thread that uses them, so on a NUMA machine they're allocated on
+                // its node.
+                let mut a = vec![1.0; elements];
+                let mut b = vec![2.0; elements];
+                let mut c = vec![0.0; elements];
+                barrier.wait();
+
+                loop {
+                    barrier.wait();
+                    if stop.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    kernel.run(&mut a, &mut b, &mut c);
+                    barrier.wait();
+                }
+            })
+        })
+        .collect();
+
+    barrier.wait();
+    MemoryBandwidthTest {
+        barrier,
+        stop,
+        threads,
+    }
+}
+
+fn memory_bandwidth_iteration(test: &mut MemoryBandwidthTest) -> bool {
+    test.barrier.wait();
+    test.barrier.wait();
+    true
+}
+
+impl Drop for MemoryBandwidthTest {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.barrier.wait();
+        for thread in self.threads.drain(..) {
+            thread.join().unwrap();
+        }
+    }
+}
+
+// Whether a thread can be pinned to every one of `cpus`, e.g. they weren't taken away by a cpuset
+// since they were listed.
+fn check_pinning(cpus: &[usize]) -> Result<(), Error> {
+    for &cpu in cpus {
+        thread::spawn(move || pin_to_cpu(cpu))
+            .join()
+            .map_err(|_| format_err!("failed to pin thread to cpu {}", cpu))??;
+    }
+    Ok(())
+}
+
+// Powers of two up to the number of cpus, and all of them.
+fn thread_counts(cpus: usize) -> Vec<usize> {
+    let mut counts: Vec<usize> = (0..)
+        .map(|power| 1 << power)
+        .take_while(|&count| count < cpus)
+        .collect();
+    counts.push(cpus);
+    counts
+}
+
+// Every kernel with 1 thread up to a thread on every cpu, to show where adding cores stops adding
+// bandwidth.
+pub struct MemoryBandwidth;
+
+impl Suite for MemoryBandwidth {
+    fn name(&self) -> &'static str {
+        "memory_bandwidth"
+    }
+
+    fn description(&self) -> &'static str {
+        "Runs the STREAM kernels (copy, scale, add, triad) and plain reads and writes over 256 MiB arrays with 1 thread up to one pinned to every cpu."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "write", "sequential", "threads"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            memory: (3 * STREAM_ARRAY_SIZE * mem::size_of::<f64>()) as u64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        STREAM_ARRAY_SIZE * mem::size_of::<f64>()
+    }
+
+    fn run(&self, options: &BenchmarkOptions) -> Result<Vec<BenchmarkResult>, Error> {
+        let cpus = available_cpus();
+        check_pinning(&cpus)?;
+        let mut results = vec![];
+        for &kernel in KERNELS {
+            for threads in thread_counts(cpus.len()) {
+                let result = benchmark(
+                    options,
+                    || Ok(memory_bandwidth_setup(kernel, &cpus[..threads])),
+                    memory_bandwidth_iteration,
+                )?;
+
+                let bytes = kernel.arrays() * STREAM_ARRAY_SIZE * mem::size_of::<f64>();
+                let per_thread = result.iterations_per_second() * bytes as f64 / threads as f64;
+                results.push(
+                    result
+                        .labelled("Memory Bandwidth", bytes)
+                        .with_parameter("kernel", kernel.name())
+                        .with_parameter("threads", &threads.to_string())
+                        .with_metric("per_thread_bytes_per_second", per_thread),
+                );
+            }
+        }
+        Ok(results)
+    }
+
+    fn estimated_runtime(&self, options: &BenchmarkOptions) -> Duration {
+        let runs = KERNELS.len() * thread_counts(available_cpus().len()).len();
+        (options.warmup + options.measure) * runs as u32
+    }
+}
@@ -0,0 +1,230 @@
+use crate::cache::AlignedBuffer;
+use crate::harness::{benchmark, black_box, BenchmarkResult};
+use crate::options::{parse_size_range, BenchmarkOptions};
+use crate::registry::{Category, Requirements, Suite};
+use crate::resources::available_memory;
+use crate::units::format_bytes;
+use failure::Error;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+use std::ptr;
+use std::time::Duration;
+
+// Every other power of two in this range is swept, unless `--copy-sizes` gives others.
+const COPY_SIZES: &str = "64B..1GiB";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CopyOperation {
+    // memcpy, through `copy_from_slice`.
+    Copy,
+    // memmove, through `ptr::copy` to 64 bytes further into the same buffer.
+    Move,
+    // memset, through `fill`.
+    Set,
+    // memset with non-temporal AVX stores, which write around the caches.
+    StreamSet,
+}
+
+pub struct CopyTest {
+    source: AlignedBuffer,
+    destination: AlignedBuffer,
+    size: usize,
+    // Changed every iteration, and written to the source before copying it, otherwise copying or
+    // setting the same bytes over and over could be optimized out of the loop.
+    value: u8,
+}
+
+// The buffers are written in full, so the iterations don't take the page faults of touching them
+// for the first time.
+fn copy_setup(operation: CopyOperation, size: usize) -> CopyTest {
+    let (source, destination) = match operation {
+        CopyOperation::Copy => (AlignedBuffer::new(size), AlignedBuffer::new(size)),
+        CopyOperation::Move => (AlignedBuffer::new(0), AlignedBuffer::new(size + 64)),
+        CopyOperation::Set | CopyOperation::StreamSet => {
+            (AlignedBuffer::new(0), AlignedBuffer::new(size))
+        }
+    };
+    let mut test = CopyTest {
+        source,
+        destination,
+        size,
+        value: 0,
+    };
+    test.source.fill(1);
+    test.destination.fill(2);
+    test
+}
+
+fn copy_iteration(operation: CopyOperation, test: &mut CopyTest) -> bool {
+    test.value = test.value.wrapping_add(1);
+    match operation {
+        CopyOperation::Copy => {
+            test.source[0] = test.value;
+            test.destination.copy_from_slice(&test.source);
+        }
+        CopyOperation::Move => unsafe {
+            let buffer = test.destination.as_mut_ptr();
+            ptr::copy(buffer, buffer.add(64), test.size);
+        },
+        CopyOperation::Set => test.destination.fill(test.value),
+        CopyOperation::StreamSet => unsafe { stream_set(&mut test.destination, test.value) },
+    }
+    black_box(&test.destination);
+    true
+}
+
+// Page-aligned buffers are aligned to the 32 bytes the stores need. Whatever is left over after
+// the last 32 bytes, all of it for sizes below 32 bytes, is set with normal stores.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn stream_set(destination: &mut [u8], value: u8) {
+    let vector = _mm256_set1_epi8(value as i8);
+    let mut chunks = destination.chunks_exact_mut(32);
+    for chunk in &mut chunks {
+        _mm256_stream_si256(chunk.as_mut_ptr() as *mut __m256i, vector);
+    }
+    chunks.into_remainder().fill(value);
+    // Non-temporal stores aren't ordered with other stores, so wait for them to be done.
+    _mm_sfence();
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+unsafe fn stream_set(_destination: &mut [u8], _value: u8) {
+    unreachable!("requires AVX2");
+}
+
+// Copies, moves or sets buffers of every size in the sweep, one result per size, to show how the
+// throughput drops once they no longer fit in the caches.
+pub struct MemoryCopy {
+    pub operation: CopyOperation,
+}
+
+impl MemoryCopy {
+    pub fn all() -> Vec<MemoryCopy> {
+        [
+            CopyOperation::Copy,
+            CopyOperation::Move,
+            CopyOperation::Set,
+            CopyOperation::StreamSet,
+        ]
+        .iter()
+        .map(|&operation| MemoryCopy { operation })
+        .collect()
+    }
+
+    fn sizes(&self, options: &BenchmarkOptions) -> Vec<usize> {
+        match &options.copy_sizes {
+            Some(sizes) => sizes.clone(),
+            None => parse_size_range(COPY_SIZES)
+                .unwrap()
+                .into_iter()
+                .step_by(2)
+                .collect(),
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self.operation {
+            CopyOperation::Copy => "Memcpy",
+            CopyOperation::Move => "Memmove",
+            CopyOperation::Set => "Memset",
+            CopyOperation::StreamSet => "Memset, Non-Temporal",
+        }
+    }
+
+    // Bytes of memory the buffers take.
+    fn memory(&self, size: usize) -> u64 {
+        match self.operation {
+            CopyOperation::Copy => 2 * size as u64,
+            _ => size as u64,
+        }
+    }
+}
+
+impl Suite for MemoryCopy {
+    fn name(&self) -> &'static str {
+        match self.operation {
+            CopyOperation::Copy => "memory_copy",
+            CopyOperation::Move => "memory_move",
+            CopyOperation::Set => "memory_set",
+            CopyOperation::StreamSet => "memory_set_non_temporal",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self.operation {
+            CopyOperation::Copy => "Copies buffers of every size in the sweep with copy_from_slice (memcpy).",
+            CopyOperation::Move => "Moves buffers of every size in the sweep 64 bytes along, overlapping themselves, with ptr::copy (memmove).",
+            CopyOperation::Set => "Sets buffers of every size in the sweep with fill (memset).",
+            CopyOperation::StreamSet => "Sets buffers of every size in the sweep with non-temporal AVX stores that bypass the caches.",
+        }
+    }
+
+    fn sweep_description(&self, options: &BenchmarkOptions) -> Option<String> {
+        let sizes = self.sizes(options);
+        Some(format!(
+            "{} sizes from {} to {}",
+            sizes.len(),
+            format_bytes(sizes[0] as u64),
+            format_bytes(sizes[sizes.len() - 1] as u64)
+        ))
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        match self.operation {
+            CopyOperation::Copy | CopyOperation::Move => &["read", "write", "sequential", "copy"],
+            CopyOperation::Set => &["write", "sequential", "copy"],
+            CopyOperation::StreamSet => &["write", "sequential", "copy", "avx2"],
+        }
+    }
+
+    // Sizes that don't fit in memory are left out of the sweep, rather than skipping it.
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            avx2: self.operation == CopyOperation::StreamSet,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        self.sizes(options)[0]
+    }
+
+    fn run(&self, options: &BenchmarkOptions) -> Result<Vec<BenchmarkResult>, Error> {
+        let operation = self.operation;
+        let mut results = vec![];
+        for size in self.sizes(options) {
+            if let Some(available) = available_memory() {
+                if self.memory(size) > available {
+                    eprintln!(
+                        "Skipping {}, needs {} of memory but only {} is available",
+                        format_bytes(size as u64),
+                        format_bytes(self.memory(size)),
+                        format_bytes(available)
+                    );
+                    continue;
+                }
+            }
+
+            let result = benchmark(
+                options,
+                || Ok(copy_setup(operation, size)),
+                |test| copy_iteration(operation, test),
+            )?;
+            results.push(
+                result
+                    .labelled(self.title(), size)
+                    .with_parameter("size", &format_bytes(size as u64)),
+            );
+        }
+        Ok(results)
+    }
+
+    fn estimated_runtime(&self, options: &BenchmarkOptions) -> Duration {
+        (options.warmup + options.measure) * self.sizes(options).len() as u32
+    }
+}
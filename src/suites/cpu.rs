@@ -0,0 +1,111 @@
+use crate::harness::black_box;
+use crate::options::BenchmarkOptions;
+use crate::registry::{Benchmark, Category, Requirements};
+use failure::Error;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+#[derive(Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub union i32simd {
+    vector: __m256i,
+    numbers: [u32; 8],
+}
+
+pub struct Simd;
+
+impl Benchmark for Simd {
+    type State = (i32simd, i32simd);
+
+    fn name(&self) -> &'static str {
+        "simd"
+    }
+
+    fn title(&self) -> &'static str {
+        "SIMD Multiply 8x i32"
+    }
+
+    fn description(&self) -> &'static str {
+        "Multiplies two vectors of eight 32-bit integers with AVX2."
+    }
+
+    fn category(&self) -> Category {
+        Category::Cpu
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["avx2"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            avx2: true,
+            ..Requirements::default()
+        }
+    }
+
+    fn setup(&self, _options: &BenchmarkOptions) -> Result<(i32simd, i32simd), Error> {
+        Ok(unsafe {
+            let a = i32simd {
+                vector: _mm256_set_epi32(1, 2, 3, 4, 5, 6, 7, 8),
+            };
+            let b = i32simd {
+                vector: _mm256_set_epi32(1, 2, 3, 4, 5, 6, 7, 8),
+            };
+            (a, b)
+        })
+    }
+
+    fn iteration(&self, (a, b): &mut (i32simd, i32simd)) -> bool {
+        unsafe {
+            let result = i32simd {
+                vector: _mm256_mul_epi32(a.vector, b.vector),
+            };
+            let result2 = i32simd {
+                vector: _mm256_mullo_epi32(a.vector, b.vector),
+            };
+            black_box(result.numbers);
+            black_box(result2.numbers);
+        }
+        true
+    }
+}
+
+const SORT_TOTAL_SIZE: usize = n_mib_bytes!(1) as usize;
+
+pub struct Sort;
+
+impl Benchmark for Sort {
+    type State = Vec<u64>;
+
+    fn name(&self) -> &'static str {
+        "sort"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sort"
+    }
+
+    fn description(&self) -> &'static str {
+        "Sorts 1 MiB of random u64s with sort_unstable."
+    }
+
+    fn category(&self) -> Category {
+        Category::Cpu
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        SORT_TOTAL_SIZE
+    }
+
+    fn setup(&self, _options: &BenchmarkOptions) -> Result<Vec<u64>, Error> {
+        let elements = SORT_TOTAL_SIZE / 8;
+        Ok((0..elements).map(|_| rand::random::<u64>()).collect())
+    }
+
+    fn iteration(&self, bytes: &mut Vec<u64>) -> bool {
+        bytes.sort_unstable();
+        // TODO: enum to re-start with setup or stop entirely
+        false
+    }
+}
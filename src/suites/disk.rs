@@ -0,0 +1,595 @@
+use crate::cache::{self, AlignedBuffer, CacheMode};
+use crate::harness::{black_box, BenchmarkResult};
+use crate::options::BenchmarkOptions;
+use crate::registry::{Benchmark, Category, Requirements};
+use crate::stats::percentile;
+use failure::Error;
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::time::Instant;
+
+// Fills the file in chunks, so the setup doesn't need as much memory as the file is large.
+fn write_zeros(file: &mut fs::File, size: usize) {
+    let chunk = vec![0; n_mib_bytes!(1) as usize];
+    let mut written = 0;
+    while written < size {
+        let length = chunk.len().min(size - written);
+        file.write_all(&chunk[..length]).unwrap();
+        written += length;
+    }
+}
+
+// Writes a file of `size` zeroes and opens it for reading, with the page cache in the state
+// `cache` and `drop_caches` ask for. The file the warmup wrote is reused for the measurement, so
+// large files are only written once.
+pub(super) fn create_file_to_read(
+    path: &Path,
+    size: usize,
+    cache: CacheMode,
+    drop_caches: bool,
+) -> fs::File {
+    let written = fs::metadata(path).is_ok_and(|metadata| metadata.len() == size as u64);
+    if !written {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)
+            .unwrap();
+        write_zeros(&mut file, size);
+        file.sync_data().unwrap();
+    }
+    cache::open_for_reading(path, cache, drop_caches).unwrap()
+}
+
+// Writes a file of `size` zeroes to overwrite in place, so the writes don't allocate blocks or
+// change the file's size. It's opened for reading too, which writable mappings need.
+pub(super) fn create_file_to_write(path: &Path, size: usize) -> fs::File {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(path)
+        .unwrap();
+    if file.metadata().unwrap().len() != size as u64 {
+        file.set_len(0).unwrap();
+        write_zeros(&mut file, size);
+        file.sync_data().unwrap();
+    }
+    file
+}
+
+// The offset of every block of a file of `file_size`, shuffled, so random reads or writes visit
+// every block once and never overlap. Otherwise this is essentially just benchmarking syscall +
+// page cache, which is going to be awfully close to random memory read. The blocks are rounded up
+// to whole pages, so the offsets are page aligned for O_DIRECT. `BenchmarkOptions::validate`
+// keeps blocks smaller than the files.
+pub(super) fn shuffled_pages(file_size: usize, block_size: usize) -> Vec<u64> {
+    let page_size = page_size::get();
+    let stride = block_size.div_ceil(page_size).max(1) * page_size;
+    let mut pages: Vec<u64> = (0..((file_size - block_size) / stride + 1))
+        .map(|i| (i * stride) as u64)
+        .collect();
+    pages.shuffle(&mut thread_rng());
+    pages
+}
+
+// Adds the IOPS and the latency percentiles of the individual requests to the result.
+pub(super) fn request_finish(
+    mut latencies: Vec<f64>,
+    queue_depth: usize,
+    result: BenchmarkResult,
+) -> BenchmarkResult {
+    latencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let iops = result.iterations_per_second() * queue_depth as f64;
+
+    result
+        .with_metric("iops", iops)
+        .with_metric("request_latency_p50_ns", percentile(&latencies, 50.0))
+        .with_metric("request_latency_p90_ns", percentile(&latencies, 90.0))
+        .with_metric("request_latency_p99_ns", percentile(&latencies, 99.0))
+        .with_metric("request_latency_p999_ns", percentile(&latencies, 99.9))
+}
+
+pub(super) const DISK_WRITE_BUF_SIZE: usize = n_kib_bytes!(8) as usize;
+
+pub struct DiskWriteTest {
+    bytes: Vec<u8>,
+    file: fs::File,
+}
+
+fn disk_write_setup(path: &Path, size_of_writes: usize) -> DiskWriteTest {
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(path)
+        .unwrap();
+
+    let bytes: Vec<u8> = (0..size_of_writes).map(|_| rand::random::<u8>()).collect();
+
+    DiskWriteTest { bytes, file }
+}
+
+pub struct DiskWriteSequentialFsync;
+
+impl Benchmark for DiskWriteSequentialFsync {
+    type State = DiskWriteTest;
+
+    fn name(&self) -> &'static str {
+        "disk_write_sequential_fsync"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sequential Disk Write, Fsync"
+    }
+
+    fn description(&self) -> &'static str {
+        "Appends 8 KiB to a file and fdatasyncs after every write."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["write", "sequential", "fsync"]
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        self.block_size(options)
+    }
+
+    fn default_block_size(&self) -> Option<usize> {
+        Some(DISK_WRITE_BUF_SIZE)
+    }
+
+    fn setup(&self, options: &BenchmarkOptions) -> Result<DiskWriteTest, Error> {
+        Ok(disk_write_setup(
+            &options.scratch_file(self.name()),
+            self.block_size(options),
+        ))
+    }
+
+    fn iteration(&self, test: &mut DiskWriteTest) -> bool {
+        test.file.write_all(&test.bytes).unwrap();
+        test.file.sync_data().unwrap();
+        true
+    }
+
+    fn teardown(&self, options: &BenchmarkOptions) {
+        let _ = fs::remove_file(options.scratch_file(self.name()));
+    }
+}
+
+pub struct DiskWriteSequentialNoFsync;
+
+impl Benchmark for DiskWriteSequentialNoFsync {
+    type State = DiskWriteTest;
+
+    fn name(&self) -> &'static str {
+        "disk_write_sequential_no_fsync"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sequential Disk Write, No Fsync"
+    }
+
+    fn description(&self) -> &'static str {
+        "Appends 8 KiB to a file without syncing, so mostly measures the page cache."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["write", "sequential"]
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        self.block_size(options)
+    }
+
+    fn default_block_size(&self) -> Option<usize> {
+        Some(DISK_WRITE_BUF_SIZE)
+    }
+
+    fn setup(&self, options: &BenchmarkOptions) -> Result<DiskWriteTest, Error> {
+        Ok(disk_write_setup(
+            &options.scratch_file(self.name()),
+            self.block_size(options),
+        ))
+    }
+
+    fn iteration(&self, test: &mut DiskWriteTest) -> bool {
+        test.file.write_all(&test.bytes).unwrap();
+        true
+    }
+
+    fn teardown(&self, options: &BenchmarkOptions) {
+        let _ = fs::remove_file(options.scratch_file(self.name()));
+    }
+}
+
+const DISK_READ_SEQUENTIAL_BUF_SIZE: usize = n_kib_bytes!(64) as usize;
+
+pub struct DiskReadSequentialTest {
+    buffer: AlignedBuffer,
+    file: fs::File,
+    cache: CacheMode,
+}
+
+pub struct DiskReadSequential;
+
+impl Benchmark for DiskReadSequential {
+    type State = DiskReadSequentialTest;
+
+    fn name(&self) -> &'static str {
+        "disk_read_sequential"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sequential Disk Read"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reads a 1 GiB file front to back in 64 KiB reads, starting over at the end."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "sequential"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            disk: n_gib_bytes!(1) as u64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        self.block_size(options)
+    }
+
+    fn default_block_size(&self) -> Option<usize> {
+        Some(DISK_READ_SEQUENTIAL_BUF_SIZE)
+    }
+
+    fn parameters(&self, options: &BenchmarkOptions) -> Vec<(&'static str, String)> {
+        vec![("cache", options.cache.to_string())]
+    }
+
+    fn setup(&self, options: &BenchmarkOptions) -> Result<DiskReadSequentialTest, Error> {
+        let path = options.scratch_file(self.name());
+        let file = create_file_to_read(
+            &path,
+            n_gib_bytes!(1) as usize,
+            options.cache,
+            options.drop_caches,
+        );
+
+        unsafe {
+            #[cfg(target_os = "linux")]
+            libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL);
+        }
+
+        Ok(DiskReadSequentialTest {
+            buffer: AlignedBuffer::new(self.block_size(options)),
+            file,
+            cache: options.cache,
+        })
+    }
+
+    fn iteration(&self, test: &mut DiskReadSequentialTest) -> bool {
+        let n = test.file.read(&mut test.buffer).unwrap();
+        // TODO: this is cheating...
+        if n < test.buffer.len() {
+            test.file.seek(SeekFrom::Start(0)).unwrap();
+            // Otherwise every pass after the first one is read from the page cache.
+            if test.cache == CacheMode::Cold {
+                cache::evict_file(&test.file).unwrap();
+            }
+        };
+        true
+    }
+
+    fn teardown(&self, options: &BenchmarkOptions) {
+        let _ = fs::remove_file(options.scratch_file(self.name()));
+    }
+}
+
+pub(super) const DISK_READ_RANDOM_BUF_SIZE: usize = n_kib_bytes!(8) as usize;
+pub(super) const DISK_READ_RANDOM_FILE_SIZE: usize = n_gib_bytes!(8) as usize;
+
+pub struct DiskReadRandomTest {
+    buffer: AlignedBuffer,
+    pages: Vec<u64>,
+    i: usize,
+    file: fs::File,
+}
+
+pub struct DiskReadRandom;
+
+impl Benchmark for DiskReadRandom {
+    type State = DiskReadRandomTest;
+
+    fn name(&self) -> &'static str {
+        "disk_read_random"
+    }
+
+    fn title(&self) -> &'static str {
+        "Random Disk Seek, No Page Cache"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reads 8 KiB at a random block of an 8 GiB file, visiting every block at most once."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "random"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            // The shuffled offset of every block in the file, for blocks as small as a page.
+            memory: (DISK_READ_RANDOM_FILE_SIZE / page_size::get() * 8) as u64,
+            disk: DISK_READ_RANDOM_FILE_SIZE as u64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        self.block_size(options)
+    }
+
+    fn default_block_size(&self) -> Option<usize> {
+        Some(DISK_READ_RANDOM_BUF_SIZE)
+    }
+
+    fn parameters(&self, options: &BenchmarkOptions) -> Vec<(&'static str, String)> {
+        vec![("cache", options.cache.to_string())]
+    }
+
+    fn setup(&self, options: &BenchmarkOptions) -> Result<DiskReadRandomTest, Error> {
+        let path = options.scratch_file(self.name());
+        let file = create_file_to_read(
+            &path,
+            DISK_READ_RANDOM_FILE_SIZE,
+            options.cache,
+            options.drop_caches,
+        );
+
+        let block_size = self.block_size(options);
+        let pages = shuffled_pages(DISK_READ_RANDOM_FILE_SIZE, block_size);
+
+        unsafe {
+            #[cfg(target_os = "linux")]
+            libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_RANDOM);
+        }
+
+        Ok(DiskReadRandomTest {
+            file,
+            pages,
+            buffer: AlignedBuffer::new(block_size),
+            i: 0,
+        })
+    }
+
+    fn iteration(&self, test: &mut DiskReadRandomTest) -> bool {
+        test.file.seek(SeekFrom::Start(test.pages[test.i])).unwrap();
+        test.file.read_exact(&mut test.buffer).unwrap();
+        black_box(&test.buffer);
+        test.i += 1;
+
+        if test.i == test.pages.len() {
+            return false;
+        };
+
+        true
+    }
+
+    fn teardown(&self, options: &BenchmarkOptions) {
+        let _ = fs::remove_file(options.scratch_file(self.name()));
+    }
+}
+
+const DISK_WRITE_RANDOM_FILE_SIZE: usize = n_gib_bytes!(1) as usize;
+
+pub struct DiskWriteRandomTest {
+    bytes: AlignedBuffer,
+    pages: Vec<u64>,
+    i: usize,
+    file: fs::File,
+    // How long every write took, in nanoseconds, including its fdatasync if it has one.
+    latencies: Vec<f64>,
+}
+
+// Overwrites blocks of a preallocated file in shuffled order, like `disk_read_random` reads them,
+// so no block is written twice.
+fn disk_write_random_setup(path: &Path, block_size: usize) -> DiskWriteRandomTest {
+    let file = create_file_to_write(path, DISK_WRITE_RANDOM_FILE_SIZE);
+
+    let pages = shuffled_pages(DISK_WRITE_RANDOM_FILE_SIZE, block_size);
+
+    let mut bytes = AlignedBuffer::new(block_size);
+    thread_rng().fill(&mut bytes[..]);
+
+    DiskWriteRandomTest {
+        bytes,
+        pages,
+        i: 0,
+        file,
+        latencies: vec![],
+    }
+}
+
+fn disk_write_random_iteration(test: &mut DiskWriteRandomTest, fsync: bool) -> bool {
+    let start = Instant::now();
+    test.file
+        .write_all_at(&test.bytes, test.pages[test.i])
+        .unwrap();
+    if fsync {
+        test.file.sync_data().unwrap();
+    }
+    test.latencies.push(start.elapsed().as_nanos() as f64);
+    test.i += 1;
+
+    test.i < test.pages.len()
+}
+
+pub struct DiskWriteRandom;
+
+impl Benchmark for DiskWriteRandom {
+    type State = DiskWriteRandomTest;
+
+    fn name(&self) -> &'static str {
+        "disk_write_random"
+    }
+
+    fn title(&self) -> &'static str {
+        "Random Disk Write, No Fsync"
+    }
+
+    fn description(&self) -> &'static str {
+        "Overwrites 8 KiB at a random block of a 1 GiB file with pwrite, visiting every block at most once, without syncing."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["write", "random"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            memory: (DISK_WRITE_RANDOM_FILE_SIZE / page_size::get() * 8) as u64,
+            disk: DISK_WRITE_RANDOM_FILE_SIZE as u64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        self.block_size(options)
+    }
+
+    fn default_block_size(&self) -> Option<usize> {
+        Some(DISK_WRITE_BUF_SIZE)
+    }
+
+    fn setup(&self, options: &BenchmarkOptions) -> Result<DiskWriteRandomTest, Error> {
+        Ok(disk_write_random_setup(
+            &options.scratch_file(self.name()),
+            self.block_size(options),
+        ))
+    }
+
+    fn iteration(&self, test: &mut DiskWriteRandomTest) -> bool {
+        disk_write_random_iteration(test, false)
+    }
+
+    fn finish(&self, test: DiskWriteRandomTest, result: BenchmarkResult) -> BenchmarkResult {
+        request_finish(test.latencies, 1, result)
+    }
+
+    fn teardown(&self, options: &BenchmarkOptions) {
+        let _ = fs::remove_file(options.scratch_file(self.name()));
+    }
+}
+
+pub struct DiskWriteRandomFsync;
+
+impl Benchmark for DiskWriteRandomFsync {
+    type State = DiskWriteRandomTest;
+
+    fn name(&self) -> &'static str {
+        "disk_write_random_fsync"
+    }
+
+    fn title(&self) -> &'static str {
+        "Random Disk Write, Fsync"
+    }
+
+    fn description(&self) -> &'static str {
+        "Overwrites 8 KiB at a random block of a 1 GiB file with pwrite and fdatasyncs after every write."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["write", "random", "fsync"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        DiskWriteRandom.requirements()
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        self.block_size(options)
+    }
+
+    fn default_block_size(&self) -> Option<usize> {
+        Some(DISK_WRITE_BUF_SIZE)
+    }
+
+    fn setup(&self, options: &BenchmarkOptions) -> Result<DiskWriteRandomTest, Error> {
+        Ok(disk_write_random_setup(
+            &options.scratch_file(self.name()),
+            self.block_size(options),
+        ))
+    }
+
+    fn iteration(&self, test: &mut DiskWriteRandomTest) -> bool {
+        disk_write_random_iteration(test, true)
+    }
+
+    fn finish(&self, test: DiskWriteRandomTest, result: BenchmarkResult) -> BenchmarkResult {
+        request_finish(test.latencies, 1, result)
+    }
+
+    fn teardown(&self, options: &BenchmarkOptions) {
+        let _ = fs::remove_file(options.scratch_file(self.name()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shuffled_pages_visit_every_block_once() {
+        let page_size = page_size::get();
+        for &block_size in &[512, page_size, 3 * page_size, 64 * page_size] {
+            let file_size = 256 * page_size;
+            let mut offsets = shuffled_pages(file_size, block_size);
+            offsets.sort();
+
+            let stride = block_size.max(page_size) as u64;
+            assert_eq!(offsets[0], 0);
+            assert!(offsets.iter().all(|offset| offset % page_size as u64 == 0));
+            assert!(offsets.windows(2).all(|pair| pair[1] - pair[0] == stride));
+            assert!(*offsets.last().unwrap() as usize + block_size <= file_size);
+            assert_eq!(offsets.len(), file_size / stride as usize);
+        }
+    }
+}
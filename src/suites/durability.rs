@@ -0,0 +1,490 @@
+use super::disk::DISK_WRITE_BUF_SIZE;
+use crate::harness::{benchmark, benchmark_with_state, BenchmarkResult};
+use crate::options::BenchmarkOptions;
+use crate::registry::{Category, Requirements, Suite};
+use crate::stats::percentile;
+use crate::units::format_bytes;
+use failure::{format_err, Error};
+use std::fs;
+use std::fs::OpenOptions;
+use std::mem;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Size of the preallocated file, which the appends wrap around in.
+const DURABILITY_FILE_SIZE: usize = n_gib_bytes!(1) as usize;
+
+// Ways of making an append durable, or in the case of sync_file_range looking like it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Strategy {
+    // fsync after every write, which also flushes metadata like the modification time.
+    Fsync,
+    // fdatasync after every write, which only flushes the metadata needed to read the data back,
+    // e.g. the file size when appending.
+    Fdatasync,
+    // The file is opened with O_DSYNC, so every write is an implicit fdatasync.
+    ODsync,
+    // The file is opened with O_SYNC, so every write is an implicit fsync.
+    OSync,
+    // sync_file_range waits for the written range to reach the disk, but neither flushes
+    // metadata nor the disk's write cache, so it isn't durable on its own.
+    SyncFileRange,
+    // fdatasync after every write to a file preallocated with fallocate, so the file size doesn't
+    // change. The first write to every block still has to mark its extent as written.
+    Fallocate,
+}
+
+const STRATEGIES: &[Strategy] = &[
+    Strategy::Fsync,
+    Strategy::Fdatasync,
+    Strategy::ODsync,
+    Strategy::OSync,
+    Strategy::SyncFileRange,
+    Strategy::Fallocate,
+];
+
+impl Strategy {
+    fn name(self) -> &'static str {
+        match self {
+            Strategy::Fsync => "fsync",
+            Strategy::Fdatasync => "fdatasync",
+            Strategy::ODsync => "o_dsync",
+            Strategy::OSync => "o_sync",
+            Strategy::SyncFileRange => "sync_file_range",
+            Strategy::Fallocate => "fallocate",
+        }
+    }
+}
+
+struct DurabilityTest {
+    bytes: Vec<u8>,
+    file: fs::File,
+    offset: u64,
+    strategy: Strategy,
+}
+
+// Allocates the first `size` bytes of the file without writing them. Not every filesystem can,
+// e.g. some NFS and overlayfs setups.
+fn preallocate(file: &fs::File, size: usize) -> Result<(), Error> {
+    let result = unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, size as i64) };
+    if result != 0 {
+        return Err(format_err!(
+            "fallocate failed: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+fn durability_setup(options: &BenchmarkOptions, strategy: Strategy, size: usize) -> DurabilityTest {
+    let mut open_options = OpenOptions::new();
+    open_options.create(true).write(true).truncate(true);
+    match strategy {
+        Strategy::ODsync => {
+            open_options.custom_flags(libc::O_DSYNC);
+        }
+        Strategy::OSync => {
+            open_options.custom_flags(libc::O_SYNC);
+        }
+        _ => {}
+    }
+    let file = open_options
+        .open(options.scratch_file(DiskWriteDurability.name()))
+        .unwrap();
+
+    if strategy == Strategy::Fallocate {
+        preallocate(&file, DURABILITY_FILE_SIZE).unwrap();
+        file.sync_all().unwrap();
+    }
+
+    DurabilityTest {
+        bytes: (0..size).map(|_| rand::random::<u8>()).collect(),
+        file,
+        offset: 0,
+        strategy,
+    }
+}
+
+fn durability_iteration(test: &mut DurabilityTest) -> bool {
+    let length = test.bytes.len() as u64;
+    test.file.write_all_at(&test.bytes, test.offset).unwrap();
+
+    match test.strategy {
+        Strategy::Fsync => test.file.sync_all().unwrap(),
+        Strategy::Fdatasync | Strategy::Fallocate => test.file.sync_data().unwrap(),
+        Strategy::ODsync | Strategy::OSync => {}
+        Strategy::SyncFileRange => {
+            let flags = libc::SYNC_FILE_RANGE_WAIT_BEFORE
+                | libc::SYNC_FILE_RANGE_WRITE
+                | libc::SYNC_FILE_RANGE_WAIT_AFTER;
+            let result = unsafe {
+                libc::sync_file_range(
+                    test.file.as_raw_fd(),
+                    test.offset as i64,
+                    length as i64,
+                    flags,
+                )
+            };
+            assert_eq!(result, 0, "{}", std::io::Error::last_os_error());
+        }
+    }
+
+    test.offset += length;
+    if test.strategy == Strategy::Fallocate && test.offset + length > DURABILITY_FILE_SIZE as u64 {
+        test.offset = 0;
+    }
+    true
+}
+
+// Appends a block at a time and makes every append durable in each of the ways above, one result
+// per strategy and block size, so they can be compared side by side.
+pub struct DiskWriteDurability;
+
+impl DiskWriteDurability {
+    fn block_sizes(&self, options: &BenchmarkOptions) -> Vec<usize> {
+        options
+            .block_sizes
+            .clone()
+            .unwrap_or_else(|| vec![options.block_size.unwrap_or(DISK_WRITE_BUF_SIZE)])
+    }
+}
+
+impl Suite for DiskWriteDurability {
+    fn name(&self) -> &'static str {
+        "disk_write_durability"
+    }
+
+    fn description(&self) -> &'static str {
+        "Appends a block to a file and makes it durable with fsync, fdatasync, O_DSYNC, O_SYNC, sync_file_range, or fdatasync on a fallocated file."
+    }
+
+    fn sweep_description(&self, options: &BenchmarkOptions) -> Option<String> {
+        Some(block_sizes_description(&self.block_sizes(options)))
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["write", "sequential", "fsync", "durability"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            linux_only: true,
+            disk: DURABILITY_FILE_SIZE as u64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        self.block_sizes(options)[0]
+    }
+
+    fn run(&self, options: &BenchmarkOptions) -> Result<Vec<BenchmarkResult>, Error> {
+        let mut results = vec![];
+        for &strategy in STRATEGIES {
+            if strategy == Strategy::Fallocate {
+                let path = options.scratch_file(self.name());
+                let supported = fs::File::create(&path)
+                    .map_err(Error::from)
+                    .and_then(|file| preallocate(&file, DISK_WRITE_BUF_SIZE));
+                let _ = fs::remove_file(&path);
+                if let Err(e) = supported {
+                    eprintln!("Skipping the fallocate strategy, {}", e);
+                    continue;
+                }
+            }
+
+            for block_size in self.block_sizes(options) {
+                let result = benchmark(
+                    options,
+                    || Ok(durability_setup(options, strategy, block_size)),
+                    durability_iteration,
+                );
+                let _ = fs::remove_file(options.scratch_file(self.name()));
+
+                let mut result = result?
+                    .labelled("Durable Disk Write", block_size)
+                    .with_parameter("strategy", strategy.name());
+                if options.block_size.is_some() || options.block_sizes.is_some() {
+                    result = result.with_parameter("block_size", &block_size.to_string());
+                }
+                results.push(result);
+            }
+        }
+        Ok(results)
+    }
+
+    fn estimated_runtime(&self, options: &BenchmarkOptions) -> Duration {
+        (options.warmup + options.measure)
+            * (STRATEGIES.len() * self.block_sizes(options).len()) as u32
+    }
+}
+
+// E.g. `block size 8 KiB`, or `3 block sizes from 4 KiB to 16 KiB` when sweeping.
+fn block_sizes_description(block_sizes: &[usize]) -> String {
+    match block_sizes {
+        [block_size] => format!("block size {}", format_bytes(*block_size as u64)),
+        _ => format!(
+            "{} block sizes from {} to {}",
+            block_sizes.len(),
+            format_bytes(block_sizes[0] as u64),
+            format_bytes(block_sizes[block_sizes.len() - 1] as u64)
+        ),
+    }
+}
+
+// Records the writers append, small like the entries of a write-ahead log.
+const GROUP_COMMIT_RECORD_SIZE: usize = 256;
+const GROUP_COMMIT_WRITERS: usize = 64;
+// The most records one fdatasync makes durable, one result per batch size.
+const GROUP_COMMIT_BATCH_SIZES: &[usize] = &[1, 2, 4, 8, 16, 32, 64];
+// The appends wrap around to the start of the file past this, like a write-ahead log recycling
+// its segments, so the file doesn't grow for as long as the measurement runs.
+const GROUP_COMMIT_FILE_SIZE: u64 = n_gib_bytes!(1) as u64;
+
+#[derive(Default)]
+struct CommitQueue {
+    // The writers only start appending once the measurement does, so no records from before it
+    // are committed during it.
+    started: bool,
+    // Where the next record is appended.
+    offset: u64,
+    // Records that joined the open batch, and how many of them are written. The syncer closes the
+    // batch once all of them are.
+    joined: usize,
+    // When every written record of the open batch was appended.
+    written: Vec<Instant>,
+    // Batches closed so far, i.e. the number of the open batch, and batches made durable so far.
+    closed: u64,
+    durable: u64,
+    committed: u64,
+    fsyncs: u64,
+    // Time from appending every record to its fdatasync completing, in nanoseconds.
+    latencies: Vec<f64>,
+    stop: bool,
+}
+
+#[derive(Default)]
+struct GroupCommit {
+    queue: Mutex<CommitQueue>,
+    // Signalled when a writer can join the open batch, when the syncer can close it, and when a
+    // batch is durable.
+    open: Condvar,
+    written: Condvar,
+    durable: Condvar,
+}
+
+struct GroupCommitTest {
+    shared: Arc<GroupCommit>,
+    threads: Vec<thread::JoinHandle<()>>,
+    // Records the measurement has waited for so far.
+    waited: u64,
+}
+
+// Every writer joins the open batch, appends its record to the file itself, and waits for the
+// batch to be made durable before appending the next one, like a transaction waiting for its
+// commit. Batches are full at `batch_size` records, and writers wait for the next one.
+fn group_commit_writer(shared: &GroupCommit, file: &fs::File, batch_size: usize) {
+    let bytes: Vec<u8> = (0..GROUP_COMMIT_RECORD_SIZE)
+        .map(|_| rand::random::<u8>())
+        .collect();
+
+    let mut queue = shared.queue.lock().unwrap();
+    loop {
+        while (!queue.started || queue.joined >= batch_size) && !queue.stop {
+            queue = shared.open.wait(queue).unwrap();
+        }
+        if queue.stop {
+            return;
+        }
+        let batch = queue.closed;
+        let offset = queue.offset;
+        queue.offset = (offset + GROUP_COMMIT_RECORD_SIZE as u64) % GROUP_COMMIT_FILE_SIZE;
+        queue.joined += 1;
+        let appended = Instant::now();
+        drop(queue);
+
+        file.write_all_at(&bytes, offset).unwrap();
+
+        queue = shared.queue.lock().unwrap();
+        queue.written.push(appended);
+        shared.written.notify_one();
+        while queue.durable <= batch && !queue.stop {
+            queue = shared.durable.wait(queue).unwrap();
+        }
+    }
+}
+
+// The syncer closes the open batch as soon as every record that joined it is written, and makes
+// them durable with a single fdatasync, while the writers append the next batch.
+fn group_commit_syncer(shared: &GroupCommit, file: &fs::File) {
+    let mut queue = shared.queue.lock().unwrap();
+    loop {
+        while (queue.written.is_empty() || queue.written.len() < queue.joined) && !queue.stop {
+            queue = shared.written.wait(queue).unwrap();
+        }
+        if queue.stop {
+            return;
+        }
+        let batch = mem::take(&mut queue.written);
+        queue.joined = 0;
+        queue.closed += 1;
+        shared.open.notify_all();
+        drop(queue);
+
+        file.sync_data().unwrap();
+
+        queue = shared.queue.lock().unwrap();
+        queue.durable += 1;
+        queue.committed += batch.len() as u64;
+        queue.fsyncs += 1;
+        for appended in batch {
+            queue.latencies.push(appended.elapsed().as_nanos() as f64);
+        }
+        shared.durable.notify_all();
+    }
+}
+
+fn group_commit_setup(options: &BenchmarkOptions, batch_size: usize) -> GroupCommitTest {
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(options.scratch_file(DiskWriteGroupCommit.name()))
+        .unwrap();
+    let file = Arc::new(file);
+
+    let shared = Arc::new(GroupCommit::default());
+    let mut threads = vec![];
+    {
+        let (shared, file) = (shared.clone(), file.clone());
+        threads.push(thread::spawn(move || group_commit_syncer(&shared, &file)));
+    }
+    for _ in 0..GROUP_COMMIT_WRITERS {
+        let (shared, file) = (shared.clone(), file.clone());
+        threads.push(thread::spawn(move || {
+            group_commit_writer(&shared, &file, batch_size)
+        }));
+    }
+
+    GroupCommitTest {
+        shared,
+        threads,
+        waited: 0,
+    }
+}
+
+// An iteration is a record committed by any of the writers, so the time per iteration is the
+// inverse of the commit throughput. The first one lets the writers start.
+fn group_commit_iteration(test: &mut GroupCommitTest) -> bool {
+    test.waited += 1;
+    let mut queue = test.shared.queue.lock().unwrap();
+    if !queue.started {
+        queue.started = true;
+        test.shared.open.notify_all();
+    }
+    while queue.committed < test.waited {
+        queue = test.shared.durable.wait(queue).unwrap();
+    }
+    true
+}
+
+impl GroupCommitTest {
+    // Stops the writers and the syncer and waits for them to exit, so they don't keep the disk
+    // busy past the measurement.
+    fn stop(&mut self) {
+        self.shared.queue.lock().unwrap().stop = true;
+        self.shared.open.notify_all();
+        self.shared.written.notify_all();
+        self.shared.durable.notify_all();
+        for thread in self.threads.drain(..) {
+            thread.join().unwrap();
+        }
+    }
+}
+
+// For the warmup's state, and the measurement's if it fails.
+impl Drop for GroupCommitTest {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Writers appending small records that a single syncer thread makes durable in batches, the way
+// a database commits its write-ahead log. One result per batch size.
+pub struct DiskWriteGroupCommit;
+
+impl Suite for DiskWriteGroupCommit {
+    fn name(&self) -> &'static str {
+        "disk_write_group_commit"
+    }
+
+    fn description(&self) -> &'static str {
+        "64 threads append 256 B records to a file and wait for a syncer thread to fdatasync them in batches, one result per cap on the batch size from 1 to 64."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["write", "sequential", "fsync", "durability"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            linux_only: true,
+            disk: GROUP_COMMIT_FILE_SIZE,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        GROUP_COMMIT_RECORD_SIZE
+    }
+
+    fn run(&self, options: &BenchmarkOptions) -> Result<Vec<BenchmarkResult>, Error> {
+        let mut results = vec![];
+        for &batch_size in GROUP_COMMIT_BATCH_SIZES {
+            let result = benchmark_with_state(
+                options,
+                || Ok(group_commit_setup(options, batch_size)),
+                group_commit_iteration,
+            );
+            let result = result.map(|(result, mut test)| {
+                test.stop();
+                (result, test)
+            });
+            let _ = fs::remove_file(options.scratch_file(self.name()));
+            let (result, test) = result?;
+
+            let (mut latencies, records_per_fsync) = {
+                let mut queue = test.shared.queue.lock().unwrap();
+                let records_per_fsync = queue.committed as f64 / queue.fsyncs.max(1) as f64;
+                (mem::take(&mut queue.latencies), records_per_fsync)
+            };
+            latencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            results.push(
+                result
+                    .labelled("Group Commit Disk Write", GROUP_COMMIT_RECORD_SIZE)
+                    .with_parameter("batch_size", &batch_size.to_string())
+                    .with_parameter("writers", &GROUP_COMMIT_WRITERS.to_string())
+                    .with_metric("records_per_fsync", records_per_fsync)
+                    .with_metric("commit_latency_p50_ns", percentile(&latencies, 50.0))
+                    .with_metric("commit_latency_p99_ns", percentile(&latencies, 99.0)),
+            );
+        }
+        Ok(results)
+    }
+
+    fn estimated_runtime(&self, options: &BenchmarkOptions) -> Duration {
+        (options.warmup + options.measure) * GROUP_COMMIT_BATCH_SIZES.len() as u32
+    }
+}
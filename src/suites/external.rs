@@ -0,0 +1,57 @@
+use crate::options::BenchmarkOptions;
+use crate::registry::{Benchmark, Category, Requirements, Service};
+use failure::Error;
+use redis::Commands;
+
+pub struct RedisReadSingleKey;
+
+impl Benchmark for RedisReadSingleKey {
+    type State = redis::Connection;
+
+    fn name(&self) -> &'static str {
+        "redis_read_single_key"
+    }
+
+    fn title(&self) -> &'static str {
+        "Redis Read"
+    }
+
+    fn description(&self) -> &'static str {
+        "GETs a 64 byte value from a local redis server."
+    }
+
+    fn category(&self) -> Category {
+        Category::ExternalService
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["redis"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            service: Some(Service {
+                name: "redis",
+                address: "127.0.0.1:6379",
+            }),
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        64
+    }
+
+    fn setup(&self, _options: &BenchmarkOptions) -> Result<redis::Connection, Error> {
+        let client = redis::Client::open("redis://127.0.0.1/")?;
+        let mut con = client.get_connection()?;
+        let bytes: Vec<u8> = (0..64).map(|_| rand::random::<u8>()).collect();
+        let _: () = con.set("1", bytes)?;
+        Ok(con)
+    }
+
+    fn iteration(&self, con: &mut redis::Connection) -> bool {
+        let _: Vec<u8> = con.get("1").unwrap();
+        true
+    }
+}
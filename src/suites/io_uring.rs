@@ -0,0 +1,511 @@
+use super::disk::{
+    create_file_to_read, create_file_to_write, request_finish, shuffled_pages,
+    DISK_READ_RANDOM_BUF_SIZE, DISK_READ_RANDOM_FILE_SIZE, DISK_WRITE_BUF_SIZE,
+};
+use crate::cache::{self, AlignedBuffer, CacheMode};
+use crate::harness::BenchmarkResult;
+use crate::options::BenchmarkOptions;
+use crate::registry::{Benchmark, Category, Requirements};
+use failure::{format_err, Error};
+use rand::Rng;
+use rio::Ordering;
+use std::fs;
+use std::fs::OpenOptions;
+use std::os::unix::io::AsRawFd;
+use std::time::Instant;
+
+// https://github.com/axboe/liburing/blob/master/examples/io_uring-cp.c
+const IO_URING_BUF_SIZE: usize = n_kib_bytes!(32) as usize;
+const IO_URING_FILE_SIZE: usize = n_gib_bytes!(1) as usize;
+// Requests submitted per iteration, all of which are in flight at once.
+const IO_URING_QUEUE_DEPTH: usize = 64;
+
+// A ring with room for `entries` submissions in flight, rather than rio's default of 256, which
+// deep queues of linked write and fsync pairs would run out of.
+fn ring(entries: usize) -> Result<rio::Rio, Error> {
+    rio::Config {
+        depth: entries.next_power_of_two().max(256),
+        ..rio::Config::default()
+    }
+    .start()
+    .map_err(|e| format_err!("failed to create an io_uring: {}", e))
+}
+
+// Where the requests go. Sequential reads start over at the start of the file once they reach its
+// end, sequential writes keep appending. Random requests go to every block once, see
+// `shuffled_pages`, and the benchmark ends when there aren't enough left for another iteration.
+enum RequestOrder {
+    Sequential { offset: u64 },
+    Random { pages: Vec<u64> },
+}
+
+impl RequestOrder {
+    fn random(file_size: usize, block_size: usize) -> RequestOrder {
+        RequestOrder::Random {
+            pages: shuffled_pages(file_size, block_size),
+        }
+    }
+
+    // Whether there are offsets left for `requests` more requests.
+    fn has_next(&self, requests: usize) -> bool {
+        match self {
+            RequestOrder::Sequential { .. } => true,
+            RequestOrder::Random { pages } => pages.len() >= requests,
+        }
+    }
+}
+
+pub struct DiskReadIoUringTest {
+    buffers: Vec<AlignedBuffer>,
+    file: fs::File,
+    file_size: u64,
+    cache: CacheMode,
+    ring: rio::Rio,
+    order: RequestOrder,
+    offsets: Vec<u64>,
+    // Time from submitting every read to its completion, in nanoseconds.
+    latencies: Vec<f64>,
+}
+
+fn io_uring_read_setup(
+    path: &std::path::Path,
+    file_size: usize,
+    block_size: usize,
+    queue_depth: usize,
+    cache: CacheMode,
+    drop_caches: bool,
+    order: RequestOrder,
+) -> Result<DiskReadIoUringTest, Error> {
+    let file = create_file_to_read(path, file_size, cache, drop_caches);
+
+    let advice = match order {
+        RequestOrder::Sequential { .. } => libc::POSIX_FADV_SEQUENTIAL,
+        RequestOrder::Random { .. } => libc::POSIX_FADV_RANDOM,
+    };
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), 0, 0, advice);
+    }
+
+    let ring = ring(queue_depth)?;
+    Ok(DiskReadIoUringTest {
+        buffers: (0..queue_depth)
+            .map(|_| AlignedBuffer::new(block_size))
+            .collect(),
+        file,
+        file_size: file_size as u64,
+        cache,
+        ring,
+        order,
+        offsets: Vec::with_capacity(queue_depth),
+        latencies: vec![],
+    })
+}
+
+fn io_uring_read_iteration(test: &mut DiskReadIoUringTest) -> bool {
+    let block_size = test.buffers[0].len() as u64;
+
+    test.offsets.clear();
+    for _ in 0..test.buffers.len() {
+        let offset = match &mut test.order {
+            RequestOrder::Sequential { offset } => {
+                if *offset + block_size > test.file_size {
+                    *offset = 0;
+                    // Otherwise every pass after the first one is read from the page cache.
+                    if test.cache == CacheMode::Cold {
+                        cache::evict_file(&test.file).unwrap();
+                    }
+                }
+                *offset += block_size;
+                *offset - block_size
+            }
+            RequestOrder::Random { pages } => pages.pop().unwrap(),
+        };
+        test.offsets.push(offset);
+    }
+
+    let (ring, file) = (&test.ring, &test.file);
+    let submitted = Instant::now();
+    let completions: Vec<_> = test
+        .buffers
+        .iter()
+        .zip(&test.offsets)
+        .map(|(buffer, offset)| ring.read_at(file, buffer, *offset))
+        .collect();
+
+    // Completions are waited for in the order they were submitted, so a read that completed
+    // before an earlier one is counted as completing with it. With all of them submitted at once
+    // that only ever overestimates the latency of the fastest reads.
+    for completion in completions {
+        completion.wait().unwrap();
+        test.latencies.push(submitted.elapsed().as_nanos() as f64);
+    }
+    test.order.has_next(test.buffers.len())
+}
+
+fn io_uring_read_finish(test: DiskReadIoUringTest, result: BenchmarkResult) -> BenchmarkResult {
+    request_finish(test.latencies, test.buffers.len(), result)
+}
+
+pub struct DiskReadSequentialIoUring;
+
+// TODO: checksum somehow
+impl Benchmark for DiskReadSequentialIoUring {
+    type State = DiskReadIoUringTest;
+
+    fn name(&self) -> &'static str {
+        "disk_read_sequential_io_uring"
+    }
+
+    fn title(&self) -> &'static str {
+        "Io-uring Sequential Disk Read"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reads a 1 GiB file front to back with io_uring, 64 reads of 32 KiB in flight at a time."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "sequential", "io_uring"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            linux_only: true,
+            disk: IO_URING_FILE_SIZE as u64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        self.block_size(options) * self.queue_depth(options)
+    }
+
+    fn default_block_size(&self) -> Option<usize> {
+        Some(IO_URING_BUF_SIZE)
+    }
+
+    fn default_queue_depth(&self) -> Option<usize> {
+        Some(IO_URING_QUEUE_DEPTH)
+    }
+
+    fn parameters(&self, options: &BenchmarkOptions) -> Vec<(&'static str, String)> {
+        vec![("cache", options.cache.to_string())]
+    }
+
+    fn setup(&self, options: &BenchmarkOptions) -> Result<DiskReadIoUringTest, Error> {
+        io_uring_read_setup(
+            &options.scratch_file(self.name()),
+            IO_URING_FILE_SIZE,
+            self.block_size(options),
+            self.queue_depth(options),
+            options.cache,
+            options.drop_caches,
+            RequestOrder::Sequential { offset: 0 },
+        )
+    }
+
+    fn iteration(&self, test: &mut DiskReadIoUringTest) -> bool {
+        io_uring_read_iteration(test)
+    }
+
+    fn finish(&self, test: DiskReadIoUringTest, result: BenchmarkResult) -> BenchmarkResult {
+        io_uring_read_finish(test, result)
+    }
+
+    fn teardown(&self, options: &BenchmarkOptions) {
+        let _ = fs::remove_file(options.scratch_file(self.name()));
+    }
+}
+
+pub struct DiskReadRandomIoUring;
+
+impl Benchmark for DiskReadRandomIoUring {
+    type State = DiskReadIoUringTest;
+
+    fn name(&self) -> &'static str {
+        "disk_read_random_io_uring"
+    }
+
+    fn title(&self) -> &'static str {
+        "Io-uring Random Disk Read"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reads 8 KiB at every block of an 8 GiB file once, in random order, with io_uring, 64 reads in flight at a time."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "random", "io_uring"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            linux_only: true,
+            // The shuffled offset of every block in the file, like `disk_read_random`.
+            memory: (DISK_READ_RANDOM_FILE_SIZE / page_size::get() * 8) as u64,
+            disk: DISK_READ_RANDOM_FILE_SIZE as u64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        self.block_size(options) * self.queue_depth(options)
+    }
+
+    // The same reads as `disk_read_random`, so the two are comparable.
+    fn default_block_size(&self) -> Option<usize> {
+        Some(DISK_READ_RANDOM_BUF_SIZE)
+    }
+
+    fn default_queue_depth(&self) -> Option<usize> {
+        Some(IO_URING_QUEUE_DEPTH)
+    }
+
+    fn parameters(&self, options: &BenchmarkOptions) -> Vec<(&'static str, String)> {
+        vec![("cache", options.cache.to_string())]
+    }
+
+    fn setup(&self, options: &BenchmarkOptions) -> Result<DiskReadIoUringTest, Error> {
+        io_uring_read_setup(
+            &options.scratch_file(self.name()),
+            DISK_READ_RANDOM_FILE_SIZE,
+            self.block_size(options),
+            self.queue_depth(options),
+            options.cache,
+            options.drop_caches,
+            RequestOrder::random(DISK_READ_RANDOM_FILE_SIZE, self.block_size(options)),
+        )
+    }
+
+    fn iteration(&self, test: &mut DiskReadIoUringTest) -> bool {
+        io_uring_read_iteration(test)
+    }
+
+    fn finish(&self, test: DiskReadIoUringTest, result: BenchmarkResult) -> BenchmarkResult {
+        io_uring_read_finish(test, result)
+    }
+
+    fn teardown(&self, options: &BenchmarkOptions) {
+        let _ = fs::remove_file(options.scratch_file(self.name()));
+    }
+}
+
+pub struct DiskWriteIoUringTest {
+    buffers: Vec<AlignedBuffer>,
+    file: fs::File,
+    ring: rio::Rio,
+    fsync: bool,
+    // Random writes overwrite a preallocated file, sequential ones append to an empty one.
+    order: RequestOrder,
+    offsets: Vec<u64>,
+    // Time from submitting every write to its completion, including its fsync if it has one.
+    latencies: Vec<f64>,
+}
+
+// Writes `queue_depth` blocks at a time, optionally linking every write to an fdatasync which the
+// ring only starts once the write completed. Relies on the vendored rio, whose fdatasync sets
+// IORING_FSYNC_DATASYNC where the kernel looks for it.
+pub struct DiskWriteIoUring {
+    pub random: bool,
+    pub fsync: bool,
+}
+
+impl DiskWriteIoUring {
+    pub fn all() -> Vec<DiskWriteIoUring> {
+        let mut suites = vec![];
+        for &random in &[false, true] {
+            for &fsync in &[false, true] {
+                suites.push(DiskWriteIoUring { random, fsync });
+            }
+        }
+        suites
+    }
+}
+
+impl Benchmark for DiskWriteIoUring {
+    type State = DiskWriteIoUringTest;
+
+    fn name(&self) -> &'static str {
+        match (self.random, self.fsync) {
+            (false, false) => "disk_write_sequential_no_fsync_io_uring",
+            (false, true) => "disk_write_sequential_fsync_io_uring",
+            (true, false) => "disk_write_random_no_fsync_io_uring",
+            (true, true) => "disk_write_random_fsync_io_uring",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match (self.random, self.fsync) {
+            (false, false) => "Io-uring Sequential Disk Write, No Fsync",
+            (false, true) => "Io-uring Sequential Disk Write, Fsync",
+            (true, false) => "Io-uring Random Disk Write, No Fsync",
+            (true, true) => "Io-uring Random Disk Write, Fsync",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match (self.random, self.fsync) {
+            (false, false) => {
+                "Appends 8 KiB to a file with io_uring, 64 writes in flight at a time, without syncing."
+            }
+            (false, true) => {
+                "Appends 8 KiB to a file with io_uring, 64 writes in flight at a time, each linked to an fdatasync."
+            }
+            (true, false) => {
+                "Overwrites 8 KiB at every block of a 1 GiB file once, in random order, with io_uring, 64 writes in flight at a time, without syncing."
+            }
+            (true, true) => {
+                "Overwrites 8 KiB at every block of a 1 GiB file once, in random order, with io_uring, 64 writes in flight at a time, each linked to an fdatasync."
+            }
+        }
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        match (self.random, self.fsync) {
+            (false, false) => &["write", "sequential", "io_uring"],
+            (false, true) => &["write", "sequential", "fsync", "io_uring"],
+            (true, false) => &["write", "random", "io_uring"],
+            (true, true) => &["write", "random", "fsync", "io_uring"],
+        }
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            linux_only: true,
+            memory: if self.random {
+                (IO_URING_FILE_SIZE / page_size::get() * 8) as u64
+            } else {
+                0
+            },
+            disk: if self.random {
+                IO_URING_FILE_SIZE as u64
+            } else {
+                0
+            },
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        self.block_size(options) * self.queue_depth(options)
+    }
+
+    // The same writes as `disk_write_sequential_fsync`, so the two are comparable.
+    fn default_block_size(&self) -> Option<usize> {
+        Some(DISK_WRITE_BUF_SIZE)
+    }
+
+    fn default_queue_depth(&self) -> Option<usize> {
+        Some(IO_URING_QUEUE_DEPTH)
+    }
+
+    fn parameters(&self, _options: &BenchmarkOptions) -> Vec<(&'static str, String)> {
+        let sync = if self.fsync { "fdatasync" } else { "none" };
+        vec![("sync", sync.to_string())]
+    }
+
+    fn setup(&self, options: &BenchmarkOptions) -> Result<DiskWriteIoUringTest, Error> {
+        let path = options.scratch_file(self.name());
+        let block_size = self.block_size(options);
+        let queue_depth = self.queue_depth(options);
+
+        let (file, order) = if self.random {
+            (
+                create_file_to_write(&path, IO_URING_FILE_SIZE),
+                RequestOrder::random(IO_URING_FILE_SIZE, block_size),
+            )
+        } else {
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&path)
+                .unwrap();
+            (file, RequestOrder::Sequential { offset: 0 })
+        };
+
+        let buffers = (0..queue_depth)
+            .map(|_| {
+                let mut buffer = AlignedBuffer::new(block_size);
+                rand::thread_rng().fill(&mut buffer[..]);
+                buffer
+            })
+            .collect();
+
+        Ok(DiskWriteIoUringTest {
+            buffers,
+            file,
+            ring: ring(if self.fsync {
+                2 * queue_depth
+            } else {
+                queue_depth
+            })?,
+            fsync: self.fsync,
+            order,
+            offsets: Vec::with_capacity(queue_depth),
+            latencies: vec![],
+        })
+    }
+
+    fn iteration(&self, test: &mut DiskWriteIoUringTest) -> bool {
+        let block_size = test.buffers[0].len() as u64;
+
+        test.offsets.clear();
+        for _ in 0..test.buffers.len() {
+            let offset = match &mut test.order {
+                RequestOrder::Sequential { offset } => {
+                    *offset += block_size;
+                    *offset - block_size
+                }
+                RequestOrder::Random { pages } => pages.pop().unwrap(),
+            };
+            test.offsets.push(offset);
+        }
+
+        let (ring, file, fsync) = (&test.ring, &test.file, test.fsync);
+        let submitted = Instant::now();
+        let completions: Vec<_> = test
+            .buffers
+            .iter()
+            .zip(&test.offsets)
+            .map(|(buffer, offset)| {
+                if fsync {
+                    let write = ring.write_at_ordered(file, buffer, *offset, Ordering::Link);
+                    (write, Some(ring.fdatasync(file)))
+                } else {
+                    (ring.write_at(file, buffer, *offset), None)
+                }
+            })
+            .collect();
+
+        // See `io_uring_read_iteration` on waiting for the completions in order.
+        for (write, fsync) in completions {
+            write.wait().unwrap();
+            if let Some(fsync) = fsync {
+                fsync.wait().unwrap();
+            }
+            test.latencies.push(submitted.elapsed().as_nanos() as f64);
+        }
+        test.order.has_next(test.buffers.len())
+    }
+
+    fn finish(&self, test: DiskWriteIoUringTest, result: BenchmarkResult) -> BenchmarkResult {
+        request_finish(test.latencies, test.buffers.len(), result)
+    }
+
+    fn teardown(&self, options: &BenchmarkOptions) {
+        let _ = fs::remove_file(options.scratch_file(self.name()));
+    }
+}
@@ -0,0 +1,509 @@
+use crate::harness::{benchmark, black_box, BenchmarkResult};
+use crate::options::{parse_size_range, BenchmarkOptions};
+use crate::registry::{Benchmark, Category, Requirements, Suite};
+use crate::resources::{available_memory, cache_sizes};
+use crate::units::format_bytes;
+use failure::Error;
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng, SeedableRng};
+use std::mem;
+use std::time::Duration;
+
+const MEMORY_SIZE_IN_ELEMENTS: usize = (n_gb_bytes!(1) / 64) as usize;
+
+pub struct MemorySequentialTest {
+    i: usize,
+    vec: Vec<[u64; 8]>,
+}
+
+pub struct MemoryWriteSequential;
+
+impl Benchmark for MemoryWriteSequential {
+    type State = MemorySequentialTest;
+
+    fn name(&self) -> &'static str {
+        "memory_write_sequential"
+    }
+
+    fn title(&self) -> &'static str {
+        "Write Seq Vec"
+    }
+
+    fn description(&self) -> &'static str {
+        "Writes a 1 GB Vec front to back, 64 bytes at a time."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["write", "sequential"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            memory: MEMORY_SIZE_IN_ELEMENTS as u64 * 64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        64
+    }
+
+    fn setup(&self, _options: &BenchmarkOptions) -> Result<MemorySequentialTest, Error> {
+        let mut vec = Vec::new();
+        vec.resize(MEMORY_SIZE_IN_ELEMENTS, [1, 2, 3, 4, 5, 6, 7, 8]);
+        Ok(MemorySequentialTest { i: 0, vec })
+    }
+
+    #[inline(always)]
+    fn iteration(&self, test: &mut MemorySequentialTest) -> bool {
+        test.vec[test.i] = [8, 7, 110694, 5, 4, 3, 2, 1];
+        black_box(test.vec[test.i]);
+        test.i += 1;
+        if test.i == test.vec.len() {
+            return false;
+        }
+        true
+    }
+}
+
+pub struct MemoryReadSequential;
+
+impl Benchmark for MemoryReadSequential {
+    type State = MemorySequentialTest;
+
+    fn name(&self) -> &'static str {
+        "memory_read_sequential"
+    }
+
+    fn title(&self) -> &'static str {
+        "Read Seq Vec"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reads a 1 GB Vec front to back, 64 bytes at a time."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "sequential"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            memory: MEMORY_SIZE_IN_ELEMENTS as u64 * 64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        64
+    }
+
+    fn setup(&self, _options: &BenchmarkOptions) -> Result<MemorySequentialTest, Error> {
+        let mut vec: Vec<[u64; 8]> = Vec::new();
+        for i in 0..MEMORY_SIZE_IN_ELEMENTS as u64 {
+            vec.push([i, i, i, i, i, i, i, i])
+        }
+
+        Ok(MemorySequentialTest { i: 0, vec })
+    }
+
+    #[inline(always)]
+    fn iteration(&self, test: &mut MemorySequentialTest) -> bool {
+        black_box(test.vec[test.i]);
+        test.i += 1;
+        if test.i == test.vec.len() {
+            return false;
+        }
+
+        true
+    }
+}
+
+pub struct MemoryRandomTest {
+    vec: Vec<[u64; 8]>,
+    order: Vec<usize>,
+    i: usize,
+}
+
+pub struct MemoryWriteRandom;
+
+impl Benchmark for MemoryWriteRandom {
+    type State = MemoryRandomTest;
+
+    fn name(&self) -> &'static str {
+        "memory_write_random"
+    }
+
+    fn title(&self) -> &'static str {
+        "Random Write Vec"
+    }
+
+    fn description(&self) -> &'static str {
+        "Writes 64 bytes at a time to a 1 GB Vec in a shuffled order."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["write", "random"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            memory: MEMORY_SIZE_IN_ELEMENTS as u64 * (64 + 8),
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        64
+    }
+
+    fn setup(&self, _options: &BenchmarkOptions) -> Result<MemoryRandomTest, Error> {
+        let mut vec = Vec::new();
+        vec.resize(MEMORY_SIZE_IN_ELEMENTS, [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let mut order: Vec<usize> = (0..MEMORY_SIZE_IN_ELEMENTS).collect();
+        order.shuffle(&mut thread_rng());
+        Ok(MemoryRandomTest { vec, order, i: 0 })
+    }
+
+    #[inline(always)]
+    fn iteration(&self, test: &mut MemoryRandomTest) -> bool {
+        test.vec[test.order[test.i]] = [8, 7, 6, 5, 4, 3, 2, 1];
+        black_box(test.vec[test.order[test.i]]);
+        test.i += 1;
+        if test.i == test.vec.len() {
+            return false;
+        }
+        true
+    }
+}
+
+pub struct MemoryReadRandom;
+
+impl Benchmark for MemoryReadRandom {
+    type State = MemoryRandomTest;
+
+    fn name(&self) -> &'static str {
+        "memory_read_random"
+    }
+
+    fn title(&self) -> &'static str {
+        "Random Read Vec"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reads 64 bytes at a time from a 1 GB Vec in a shuffled order, independent reads the CPU overlaps."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "random"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            memory: MEMORY_SIZE_IN_ELEMENTS as u64 * (64 + 8),
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        64
+    }
+
+    fn setup(&self, _options: &BenchmarkOptions) -> Result<MemoryRandomTest, Error> {
+        let mut vec = Vec::new();
+        vec.resize(MEMORY_SIZE_IN_ELEMENTS, [1, 2, 3, 4, 5, 6, 7, 8]);
+        unsafe {
+            let data = vec.as_mut_ptr() as *mut libc::c_void;
+            libc::madvise(data, MEMORY_SIZE_IN_ELEMENTS, libc::MADV_RANDOM);
+        }
+
+        let mut order: Vec<usize> = (0..MEMORY_SIZE_IN_ELEMENTS).collect();
+        unsafe {
+            let data = order.as_mut_ptr() as *mut libc::c_void;
+            libc::madvise(data, MEMORY_SIZE_IN_ELEMENTS, libc::MADV_SEQUENTIAL);
+        }
+        order.shuffle(&mut thread_rng());
+        Ok(MemoryRandomTest { vec, order, i: 0 })
+    }
+
+    #[inline(always)]
+    fn iteration(&self, test: &mut MemoryRandomTest) -> bool {
+        black_box(test.vec[test.order[test.i]]);
+        test.i += 1;
+        if test.i == test.vec.len() {
+            return false;
+        }
+        true
+    }
+}
+
+// The working set sizes `memory_read_working_set` sweeps by default, from well within L1 to well
+// past any L3.
+const WORKING_SET_SIZES: &str = "4KiB..4GiB";
+
+// A cache line holding the index of the next line to read, so every read depends on the one
+// before it and the CPU can't have more than one in flight.
+#[derive(Clone, Copy)]
+#[repr(align(64))]
+struct Line {
+    next: usize,
+}
+
+pub struct PointerChaseTest {
+    lines: Vec<Line>,
+    position: usize,
+}
+
+// Links `size` bytes of lines into a single random cycle with Sattolo's algorithm, so the chase
+// visits every line before it repeats and the prefetchers can't guess the next one.
+fn pointer_chase_setup(size: usize) -> PointerChaseTest {
+    let mut lines: Vec<Line> = (0..size / mem::size_of::<Line>())
+        .map(|next| Line { next })
+        .collect();
+    let mut rng = SmallRng::from_entropy();
+    for i in (1..lines.len()).rev() {
+        let j = rng.gen_range(0, i);
+        let next = lines[i].next;
+        lines[i].next = lines[j].next;
+        lines[j].next = next;
+    }
+    PointerChaseTest { lines, position: 0 }
+}
+
+fn pointer_chase_iteration(test: &mut PointerChaseTest) -> bool {
+    test.position = black_box(test.lines[test.position].next);
+    true
+}
+
+// The smallest cache a working set fits in, going by the sizes in /sys.
+fn cache_level(size: usize, caches: &[(String, u64)]) -> String {
+    caches
+        .iter()
+        .find(|(_, cache_size)| size as u64 <= *cache_size)
+        .map(|(level, _)| level.clone())
+        .unwrap_or_else(|| String::from("DRAM"))
+}
+
+// Chases pointers through working sets of every size in the sweep, one result per size, so the
+// latency steps up as the working set outgrows every level of the cache hierarchy.
+pub struct MemoryReadWorkingSet;
+
+impl MemoryReadWorkingSet {
+    fn sizes(&self, options: &BenchmarkOptions) -> Vec<usize> {
+        options
+            .working_sets
+            .clone()
+            .unwrap_or_else(|| parse_size_range(WORKING_SET_SIZES).unwrap())
+    }
+}
+
+impl Suite for MemoryReadWorkingSet {
+    fn name(&self) -> &'static str {
+        "memory_read_working_set"
+    }
+
+    fn description(&self) -> &'static str {
+        "Chases pointers through a random cycle of cache lines in working sets from 4 KiB to 4 GiB, annotated with the cache they fit in."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "random", "latency"]
+    }
+
+    // Working sets that don't fit in memory are left out of the sweep, rather than skipping it.
+    fn requirements(&self) -> Requirements {
+        Requirements::default()
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        mem::size_of::<Line>()
+    }
+
+    fn run(&self, options: &BenchmarkOptions) -> Result<Vec<BenchmarkResult>, Error> {
+        let caches = cache_sizes();
+        let mut results = vec![];
+        for size in self.sizes(options) {
+            if let Some(available) = available_memory() {
+                if size as u64 > available {
+                    eprintln!(
+                        "Skipping the {} working set, only {} of memory is available",
+                        format_bytes(size as u64),
+                        format_bytes(available)
+                    );
+                    continue;
+                }
+            }
+
+            let result = benchmark(
+                options,
+                || Ok(pointer_chase_setup(size)),
+                pointer_chase_iteration,
+            )?;
+            results.push(
+                result
+                    .labelled("Pointer Chase", mem::size_of::<Line>())
+                    .with_parameter("working_set", &format_bytes(size as u64))
+                    .with_parameter("fits_in", &cache_level(size, &caches)),
+            );
+        }
+        Ok(results)
+    }
+
+    fn estimated_runtime(&self, options: &BenchmarkOptions) -> Duration {
+        (options.warmup + options.measure) * self.sizes(options).len() as u32
+    }
+}
+
+// Random reads of the same 1 GB, once dependent on each other by chasing pointers and once
+// independent like `memory_read_random`, side by side. The dependent loads are the latency of a
+// random memory read, the independent ones the throughput the CPU gets by overlapping them.
+pub struct MemoryReadLatency;
+
+impl Suite for MemoryReadLatency {
+    fn name(&self) -> &'static str {
+        "memory_read_latency"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reads 64 bytes at random from a 1 GB Vec, once chasing pointers so every read waits for the last and once with independent reads."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "random", "latency"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Benchmark::requirements(&MemoryReadRandom)
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        64
+    }
+
+    fn run(&self, options: &BenchmarkOptions) -> Result<Vec<BenchmarkResult>, Error> {
+        let dependent = benchmark(
+            options,
+            || Ok(pointer_chase_setup(MEMORY_SIZE_IN_ELEMENTS * 64)),
+            pointer_chase_iteration,
+        )?;
+        let independent = benchmark(
+            options,
+            || MemoryReadRandom.setup(options),
+            |test| MemoryReadRandom.iteration(test),
+        )?;
+
+        Ok(vec![
+            dependent
+                .labelled("Random Read Vec, Dependent", 64)
+                .with_parameter("loads", "dependent"),
+            independent
+                .labelled("Random Read Vec, Independent", 64)
+                .with_parameter("loads", "independent"),
+        ])
+    }
+
+    fn estimated_runtime(&self, options: &BenchmarkOptions) -> Duration {
+        (options.warmup + options.measure) * 2
+    }
+}
+
+// Numbers of pointer chains `memory_read_parallel` interleaves, one result each.
+const MEMORY_LEVEL_PARALLELISM: &[usize] = &[1, 2, 4, 8, 16, 32];
+
+// Follows K chains through the same random cycle of cache lines at once, a step of each per
+// iteration. The reads within a chain depend on each other, but the K chains don't, so the CPU
+// can have up to K misses in flight, like a batch of hash table lookups.
+pub struct MemoryReadParallel;
+
+impl Suite for MemoryReadParallel {
+    fn name(&self) -> &'static str {
+        "memory_read_parallel"
+    }
+
+    fn description(&self) -> &'static str {
+        "Chases 1 to 32 interleaved pointer chains through a 1 GB Vec, to show how many random reads the CPU overlaps."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "random", "latency"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            memory: MEMORY_SIZE_IN_ELEMENTS as u64 * 64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        64
+    }
+
+    fn run(&self, options: &BenchmarkOptions) -> Result<Vec<BenchmarkResult>, Error> {
+        // Building the cycle takes a while, so every run shares it and only starts its chains
+        // at different lines.
+        let lines = pointer_chase_setup(MEMORY_SIZE_IN_ELEMENTS * 64).lines;
+
+        let mut results = vec![];
+        for &chains in MEMORY_LEVEL_PARALLELISM {
+            let result = benchmark(
+                options,
+                || {
+                    let mut rng = SmallRng::from_entropy();
+                    Ok((0..chains)
+                        .map(|_| rng.gen_range(0, lines.len()))
+                        .collect::<Vec<usize>>())
+                },
+                |positions| {
+                    for position in positions.iter_mut() {
+                        *position = black_box(lines[*position].next);
+                    }
+                    true
+                },
+            )?;
+
+            let per_read = result.nanoseconds_per_iteration() / chains as f64;
+            results.push(
+                result
+                    .labelled("Parallel Random Read Vec", 64 * chains)
+                    .with_parameter("chains", &chains.to_string())
+                    .with_metric("per_read_ns", per_read),
+            );
+        }
+        Ok(results)
+    }
+
+    fn estimated_runtime(&self, options: &BenchmarkOptions) -> Duration {
+        (options.warmup + options.measure) * MEMORY_LEVEL_PARALLELISM.len() as u32
+    }
+}
@@ -0,0 +1,386 @@
+use super::disk::{
+    create_file_to_read, create_file_to_write, shuffled_pages, DISK_READ_RANDOM_BUF_SIZE,
+    DISK_READ_RANDOM_FILE_SIZE, DISK_WRITE_BUF_SIZE,
+};
+use crate::cache::{self, CacheMode};
+use crate::harness::black_box;
+use crate::mmap::{Advice, Mapping};
+use crate::options::BenchmarkOptions;
+use crate::registry::{Benchmark, Category, Requirements};
+use failure::Error;
+use rand::{thread_rng, Rng};
+use std::fs;
+
+// The same as `disk_read_sequential`, so the two are comparable.
+const MMAP_READ_SEQUENTIAL_BUF_SIZE: usize = n_kib_bytes!(64) as usize;
+const MMAP_FILE_SIZE: usize = n_gib_bytes!(1) as usize;
+
+// The madvise hint and MAP_POPULATE, reported with the results of every mmap suite.
+fn mmap_parameters(options: &BenchmarkOptions, advice: Advice) -> Vec<(&'static str, String)> {
+    vec![
+        ("madvise", options.madvise.unwrap_or(advice).to_string()),
+        ("populate", options.populate.to_string()),
+    ]
+}
+
+fn map(
+    file: &fs::File,
+    size: usize,
+    writable: bool,
+    options: &BenchmarkOptions,
+    advice: Advice,
+) -> Mapping {
+    let mapping = Mapping::new(file, size, writable, options.populate).unwrap();
+    mapping.advise(options.madvise.unwrap_or(advice)).unwrap();
+    mapping
+}
+
+// Mappings always go through the page cache, so `--cache direct` is the same as cold, and is
+// reported as such.
+fn mmap_cache(options: &BenchmarkOptions) -> CacheMode {
+    match options.cache {
+        CacheMode::Warm => CacheMode::Warm,
+        CacheMode::Cold | CacheMode::Direct => CacheMode::Cold,
+    }
+}
+
+pub struct MmapReadTest {
+    // Mapped before the file, so it's unmapped before the file is closed.
+    mapping: Mapping,
+    file: fs::File,
+    buffer: Vec<u8>,
+    cache: CacheMode,
+    offset: usize,
+    pages: Vec<u64>,
+    i: usize,
+}
+
+fn mmap_read_setup(
+    options: &BenchmarkOptions,
+    path: &std::path::Path,
+    file_size: usize,
+    block_size: usize,
+    advice: Advice,
+) -> MmapReadTest {
+    let cache = mmap_cache(options);
+    let file = create_file_to_read(path, file_size, cache, options.drop_caches);
+    let mapping = map(&file, file_size, false, options, advice);
+    MmapReadTest {
+        mapping,
+        file,
+        buffer: vec![0; block_size],
+        cache,
+        offset: 0,
+        pages: vec![],
+        i: 0,
+    }
+}
+
+pub struct DiskReadSequentialMmap;
+
+impl Benchmark for DiskReadSequentialMmap {
+    type State = MmapReadTest;
+
+    fn name(&self) -> &'static str {
+        "disk_read_sequential_mmap"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sequential Disk Read, Mmap"
+    }
+
+    fn description(&self) -> &'static str {
+        "Copies a mapped 1 GiB file front to back 64 KiB at a time, starting over at the end."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "sequential", "mmap"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            disk: MMAP_FILE_SIZE as u64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        self.block_size(options)
+    }
+
+    fn default_block_size(&self) -> Option<usize> {
+        Some(MMAP_READ_SEQUENTIAL_BUF_SIZE)
+    }
+
+    fn parameters(&self, options: &BenchmarkOptions) -> Vec<(&'static str, String)> {
+        let mut parameters = vec![("cache", mmap_cache(options).to_string())];
+        parameters.extend(mmap_parameters(options, Advice::Sequential));
+        parameters
+    }
+
+    fn setup(&self, options: &BenchmarkOptions) -> Result<MmapReadTest, Error> {
+        Ok(mmap_read_setup(
+            options,
+            &options.scratch_file(self.name()),
+            MMAP_FILE_SIZE,
+            self.block_size(options),
+            Advice::Sequential,
+        ))
+    }
+
+    fn iteration(&self, test: &mut MmapReadTest) -> bool {
+        let length = test.buffer.len();
+        if test.offset + length > test.mapping.len() {
+            test.offset = 0;
+            // Mapped pages can't be evicted, so they're unmapped first.
+            if test.cache == CacheMode::Cold {
+                test.mapping.drop_pages().unwrap();
+                cache::evict_file(&test.file).unwrap();
+            }
+        }
+        test.buffer
+            .copy_from_slice(&test.mapping[test.offset..test.offset + length]);
+        black_box(&test.buffer);
+        test.offset += length;
+        true
+    }
+
+    fn teardown(&self, options: &BenchmarkOptions) {
+        let _ = fs::remove_file(options.scratch_file(self.name()));
+    }
+}
+
+pub struct DiskReadRandomMmap;
+
+impl Benchmark for DiskReadRandomMmap {
+    type State = MmapReadTest;
+
+    fn name(&self) -> &'static str {
+        "disk_read_random_mmap"
+    }
+
+    fn title(&self) -> &'static str {
+        "Random Disk Read, Mmap"
+    }
+
+    fn description(&self) -> &'static str {
+        "Copies 8 KiB at a random block of a mapped 8 GiB file, visiting every block at most once."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "random", "mmap"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            memory: (DISK_READ_RANDOM_FILE_SIZE / page_size::get() * 8) as u64,
+            disk: DISK_READ_RANDOM_FILE_SIZE as u64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        self.block_size(options)
+    }
+
+    // The same reads as `disk_read_random`, so the two are comparable.
+    fn default_block_size(&self) -> Option<usize> {
+        Some(DISK_READ_RANDOM_BUF_SIZE)
+    }
+
+    fn parameters(&self, options: &BenchmarkOptions) -> Vec<(&'static str, String)> {
+        let mut parameters = vec![("cache", mmap_cache(options).to_string())];
+        parameters.extend(mmap_parameters(options, Advice::Random));
+        parameters
+    }
+
+    fn setup(&self, options: &BenchmarkOptions) -> Result<MmapReadTest, Error> {
+        let block_size = self.block_size(options);
+        let mut test = mmap_read_setup(
+            options,
+            &options.scratch_file(self.name()),
+            DISK_READ_RANDOM_FILE_SIZE,
+            block_size,
+            Advice::Random,
+        );
+        test.pages = shuffled_pages(DISK_READ_RANDOM_FILE_SIZE, block_size);
+        Ok(test)
+    }
+
+    fn iteration(&self, test: &mut MmapReadTest) -> bool {
+        let offset = test.pages[test.i] as usize;
+        let length = test.buffer.len();
+        test.buffer
+            .copy_from_slice(&test.mapping[offset..offset + length]);
+        black_box(&test.buffer);
+        test.i += 1;
+        test.i < test.pages.len()
+    }
+
+    fn teardown(&self, options: &BenchmarkOptions) {
+        let _ = fs::remove_file(options.scratch_file(self.name()));
+    }
+}
+
+pub struct MmapWriteTest {
+    mapping: Mapping,
+    bytes: Vec<u8>,
+    msync: bool,
+    // Shuffled block offsets for random writes, sequential writes wrap around the file.
+    pages: Option<Vec<u64>>,
+    offset: usize,
+    i: usize,
+}
+
+// Writes blocks to a mapped, preallocated 1 GiB file, optionally with an msync of the written
+// pages after every write. Sequential writes wrap around the file and random ones visit every
+// block once, like `disk_write_random`.
+pub struct DiskWriteMmap {
+    pub random: bool,
+    pub msync: bool,
+}
+
+impl DiskWriteMmap {
+    pub fn all() -> Vec<DiskWriteMmap> {
+        let mut suites = vec![];
+        for &random in &[false, true] {
+            for &msync in &[false, true] {
+                suites.push(DiskWriteMmap { random, msync });
+            }
+        }
+        suites
+    }
+}
+
+impl Benchmark for DiskWriteMmap {
+    type State = MmapWriteTest;
+
+    fn name(&self) -> &'static str {
+        match (self.random, self.msync) {
+            (false, false) => "disk_write_sequential_mmap",
+            (false, true) => "disk_write_sequential_msync_mmap",
+            (true, false) => "disk_write_random_mmap",
+            (true, true) => "disk_write_random_msync_mmap",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match (self.random, self.msync) {
+            (false, false) => "Sequential Disk Write, Mmap, No Msync",
+            (false, true) => "Sequential Disk Write, Mmap, Msync",
+            (true, false) => "Random Disk Write, Mmap, No Msync",
+            (true, true) => "Random Disk Write, Mmap, Msync",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match (self.random, self.msync) {
+            (false, false) => "Copies 8 KiB at a time into a mapped 1 GiB file front to back, without syncing.",
+            (false, true) => "Copies 8 KiB at a time into a mapped 1 GiB file front to back, and msyncs after every write.",
+            (true, false) => "Copies 8 KiB into a random block of a mapped 1 GiB file, visiting every block at most once, without syncing.",
+            (true, true) => "Copies 8 KiB into a random block of a mapped 1 GiB file, visiting every block at most once, and msyncs after every write.",
+        }
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        match (self.random, self.msync) {
+            (false, false) => &["write", "sequential", "mmap"],
+            (false, true) => &["write", "sequential", "fsync", "mmap"],
+            (true, false) => &["write", "random", "mmap"],
+            (true, true) => &["write", "random", "fsync", "mmap"],
+        }
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            memory: if self.random {
+                (MMAP_FILE_SIZE / page_size::get() * 8) as u64
+            } else {
+                0
+            },
+            disk: MMAP_FILE_SIZE as u64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, options: &BenchmarkOptions) -> usize {
+        self.block_size(options)
+    }
+
+    // The same writes as `disk_write_sequential_fsync`, so the two are comparable.
+    fn default_block_size(&self) -> Option<usize> {
+        Some(DISK_WRITE_BUF_SIZE)
+    }
+
+    fn parameters(&self, options: &BenchmarkOptions) -> Vec<(&'static str, String)> {
+        let advice = if self.random {
+            Advice::Random
+        } else {
+            Advice::Sequential
+        };
+        mmap_parameters(options, advice)
+    }
+
+    fn setup(&self, options: &BenchmarkOptions) -> Result<MmapWriteTest, Error> {
+        let block_size = self.block_size(options);
+        let file = create_file_to_write(&options.scratch_file(self.name()), MMAP_FILE_SIZE);
+        let (advice, pages) = if self.random {
+            (
+                Advice::Random,
+                Some(shuffled_pages(MMAP_FILE_SIZE, block_size)),
+            )
+        } else {
+            (Advice::Sequential, None)
+        };
+
+        let mut bytes = vec![0; block_size];
+        thread_rng().fill(&mut bytes[..]);
+
+        Ok(MmapWriteTest {
+            mapping: map(&file, MMAP_FILE_SIZE, true, options, advice),
+            bytes,
+            msync: self.msync,
+            pages,
+            offset: 0,
+            i: 0,
+        })
+    }
+
+    fn iteration(&self, test: &mut MmapWriteTest) -> bool {
+        let length = test.bytes.len();
+        let offset = match &test.pages {
+            Some(pages) => pages[test.i] as usize,
+            None => {
+                if test.offset + length > test.mapping.len() {
+                    test.offset = 0;
+                }
+                test.offset += length;
+                test.offset - length
+            }
+        };
+
+        test.mapping[offset..offset + length].copy_from_slice(&test.bytes);
+        if test.msync {
+            test.mapping.sync(offset, length).unwrap();
+        }
+
+        test.i += 1;
+        test.pages.as_ref().is_none_or(|pages| test.i < pages.len())
+    }
+
+    fn teardown(&self, options: &BenchmarkOptions) {
+        let _ = fs::remove_file(options.scratch_file(self.name()));
+    }
+}
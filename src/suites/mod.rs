@@ -0,0 +1,90 @@
+// The suites `base-rates` runs, grouped by what they measure.
+mod bandwidth;
+mod copy;
+mod cpu;
+mod disk;
+#[cfg(target_os = "linux")]
+mod durability;
+mod external;
+#[cfg(target_os = "linux")]
+mod io_uring;
+mod memory;
+mod mmap;
+mod network;
+mod numa;
+mod page_fault;
+mod syscall;
+
+pub use bandwidth::*;
+pub use copy::*;
+pub use cpu::*;
+pub use disk::*;
+#[cfg(target_os = "linux")]
+pub use durability::*;
+pub use external::*;
+#[cfg(target_os = "linux")]
+pub use io_uring::*;
+pub use memory::*;
+pub use mmap::*;
+pub use network::*;
+pub use numa::*;
+pub use page_fault::*;
+pub use syscall::*;
+
+use crate::registry::Registry;
+
+// Every suite in the order they run in.
+pub fn builtin() -> Registry {
+    let mut registry = Registry::new();
+    registry
+        .register(MemoryReadSequential)
+        .register(MemoryWriteSequential)
+        .register(MemoryReadRandom)
+        .register(MemoryWriteRandom)
+        .register(MemoryReadLatency)
+        .register(MemoryReadParallel)
+        .register(MemoryBandwidth)
+        .register(MemoryNuma)
+        .register(MemoryReadWorkingSet)
+        .register(MemoryPageFaultMinor)
+        .register(MemoryPageFaultHuge);
+    for suite in MemoryCopy::all() {
+        registry.register(suite);
+    }
+    registry
+        .register(SyscallGetpid)
+        .register(SyscallTime)
+        .register(SyscallGetrusage)
+        .register(SyscallStat)
+        .register(DiskReadSequential)
+        .register(DiskReadRandom)
+        .register(DiskWriteSequentialNoFsync);
+    #[cfg(target_os = "linux")]
+    registry
+        .register(DiskReadSequentialIoUring)
+        .register(DiskReadRandomIoUring);
+    #[cfg(target_os = "linux")]
+    for suite in DiskWriteIoUring::all() {
+        registry.register(suite);
+    }
+    registry
+        .register(DiskWriteSequentialFsync)
+        .register(DiskWriteRandom)
+        .register(DiskWriteRandomFsync)
+        .register(DiskReadSequentialMmap)
+        .register(DiskReadRandomMmap)
+        .register(DiskPageFaultMajor);
+    for suite in DiskWriteMmap::all() {
+        registry.register(suite);
+    }
+    #[cfg(target_os = "linux")]
+    registry
+        .register(DiskWriteDurability)
+        .register(DiskWriteGroupCommit);
+    registry
+        .register(TcpReadWrite)
+        .register(Simd)
+        .register(RedisReadSingleKey)
+        .register(Sort);
+    registry
+}
@@ -0,0 +1,135 @@
+use crate::options::BenchmarkOptions;
+use crate::registry::{Benchmark, Category};
+use failure::Error;
+use std::io;
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+pub struct TcpEchoTest {
+    stream: TcpStream,
+    bytes: Vec<u8>,
+    buffer: [u8; 64],
+}
+
+fn tcp_set_options(stream: &TcpStream) {
+    stream.set_nodelay(true).unwrap();
+    stream.set_nonblocking(false).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_millis(1000)))
+        .unwrap();
+    stream
+        .set_write_timeout(Some(Duration::from_millis(1000)))
+        .unwrap();
+}
+
+pub struct TcpReadWrite;
+
+impl Benchmark for TcpReadWrite {
+    type State = TcpEchoTest;
+
+    fn name(&self) -> &'static str {
+        "tcp_read_write"
+    }
+
+    fn title(&self) -> &'static str {
+        "Tcp Echo"
+    }
+
+    fn description(&self) -> &'static str {
+        "Sends 64 bytes to an echo server over loopback TCP and reads them back."
+    }
+
+    fn category(&self) -> Category {
+        Category::Network
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["tcp", "loopback"]
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        64
+    }
+
+    // Every setup gets its own server on a free port, which exits once the client hangs up.
+    fn setup(&self, _options: &BenchmarkOptions) -> Result<TcpEchoTest, Error> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+
+        // This server doesn't support multiple clients.
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            tcp_set_options(&stream);
+
+            let mut buffer: [u8; 64] = [0; 64];
+
+            loop {
+                match stream.read(&mut buffer) {
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        // println!("s: failed to read, err: {:?}..", e);
+                        continue;
+                    }
+                    Ok(0) => break,
+                    Ok(n) => {
+                        // println!("s: read: {}", n);
+
+                        match stream.write(&buffer[..n]) {
+                            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                                // println!("s: failed to write");
+                                continue;
+                            }
+                            Ok(_n) => {
+                                // println!("s: write: {}", n);
+                            }
+                            Err(e) => panic!("{}", e),
+                        };
+                    }
+                    Err(e) => panic!("{}", e),
+                };
+            }
+        });
+
+        let stream = TcpStream::connect(address)?;
+        tcp_set_options(&stream);
+
+        Ok(TcpEchoTest {
+            stream,
+            bytes: (0..64).map(|_| rand::random::<u8>()).collect(),
+            buffer: [0; 64],
+        })
+    }
+
+    fn iteration(&self, test: &mut TcpEchoTest) -> bool {
+        match test.stream.write(&test.bytes) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                // println!("c: failed to write");
+                return true;
+            }
+            Ok(n) => {
+                // println!("c: write: {}", n);
+
+                match test.stream.read(&mut test.buffer[0..n]) {
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        // println!("c: failed to read, err: {:?}..", e);
+                        return true;
+                    }
+                    Ok(_n) => {
+                        // println!("c: read: {}\n", n);
+                    }
+                    Err(e) => {
+                        // println!("omgs read! {:?}", e.raw_os_error());
+                        panic!("{}", e)
+                    }
+                };
+            }
+            Err(e) => {
+                // println!("omgs write! {:?}", e.raw_os_error());
+                panic!("{}", e)
+            }
+        };
+
+        true
+    }
+}
@@ -0,0 +1,156 @@
+use crate::harness::{benchmark, black_box, BenchmarkResult};
+use crate::mmap::Mapping;
+use crate::options::BenchmarkOptions;
+use crate::registry::{Category, Requirements, Suite};
+use crate::topology::{available_cpus, bind_to_node, numa_nodes, pin_to_cpus, Node};
+use failure::Error;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use std::time::Duration;
+
+const NUMA_MEMORY_SIZE: usize = n_gb_bytes!(1) as usize;
+const LINE_SIZE: usize = 64;
+
+pub struct NumaTest {
+    memory: Mapping,
+    // Lines in the order they're read, empty to read them front to back.
+    order: Vec<usize>,
+    i: usize,
+}
+
+// Whether memory can be bound to `node`, which mbind can be denied, e.g. in containers restricted
+// to some of the nodes. Checked once before `numa_setup` binds the real memory.
+fn check_binding(node: usize) -> Result<(), Error> {
+    let memory = Mapping::anonymous(page_size::get())?;
+    bind_to_node(&memory, node)
+}
+
+// Allocates the memory on `node` and touches all of it, so it's there before the measurement.
+fn numa_setup(node: usize, random: bool) -> NumaTest {
+    let mut memory = Mapping::anonymous(NUMA_MEMORY_SIZE).unwrap();
+    bind_to_node(&memory, node).unwrap();
+    memory.fill(1);
+
+    let mut order = vec![];
+    if random {
+        order = (0..NUMA_MEMORY_SIZE / LINE_SIZE).collect();
+        order.shuffle(&mut thread_rng());
+    }
+    NumaTest {
+        memory,
+        order,
+        i: 0,
+    }
+}
+
+fn numa_iteration(test: &mut NumaTest) -> bool {
+    let line = if test.order.is_empty() {
+        test.i
+    } else {
+        test.order[test.i]
+    };
+    let line = &test.memory[line * LINE_SIZE..(line + 1) * LINE_SIZE];
+    black_box(unsafe { *(line.as_ptr() as *const [u64; 8]) });
+    test.i += 1;
+    test.i < NUMA_MEMORY_SIZE / LINE_SIZE
+}
+
+// The sequential and random reads of `memory_read_sequential` and `memory_read_random`, from a
+// thread pinned to the first NUMA node, of memory bound to that node and to the next one.
+pub struct MemoryNuma;
+
+impl MemoryNuma {
+    // The node the thread runs on, and the node its memory is on for the local and remote runs.
+    fn placements(&self, nodes: &[Node]) -> Vec<(&'static str, usize)> {
+        let mut placements = vec![("local", nodes[0].id)];
+        if nodes.len() > 1 {
+            placements.push(("remote", nodes[1].id));
+        }
+        placements
+    }
+}
+
+impl Suite for MemoryNuma {
+    fn name(&self) -> &'static str {
+        "memory_numa"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reads 1 GB sequentially and at random, 64 bytes at a time, from memory on the same NUMA node as the thread and on another one."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "sequential", "random", "numa"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            linux_only: true,
+            memory: (NUMA_MEMORY_SIZE + NUMA_MEMORY_SIZE / LINE_SIZE * 8) as u64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        LINE_SIZE
+    }
+
+    fn run(&self, options: &BenchmarkOptions) -> Result<Vec<BenchmarkResult>, Error> {
+        let mut nodes = numa_nodes();
+        if nodes.is_empty() {
+            nodes.push(Node {
+                id: 0,
+                cpus: available_cpus(),
+            });
+        }
+        if nodes.len() == 1 {
+            eprintln!("Only one NUMA node, so only local results are available");
+        }
+
+        // The benchmark runs on this thread, which is only pinned for as long as it runs.
+        let cpus = available_cpus();
+        pin_to_cpus(&nodes[0].cpus)?;
+        let results = self.measure(options, &nodes);
+        pin_to_cpus(&cpus)?;
+        results
+    }
+
+    fn estimated_runtime(&self, options: &BenchmarkOptions) -> Duration {
+        let nodes = numa_nodes().len().max(1);
+        (options.warmup + options.measure) * 2 * nodes.min(2) as u32
+    }
+}
+
+impl MemoryNuma {
+    fn measure(
+        &self,
+        options: &BenchmarkOptions,
+        nodes: &[Node],
+    ) -> Result<Vec<BenchmarkResult>, Error> {
+        let mut results = vec![];
+        for &(access, random) in &[("sequential", false), ("random", true)] {
+            let mut local_ns = None;
+            for (placement, node) in self.placements(nodes) {
+                check_binding(node)?;
+                let result = benchmark(options, || Ok(numa_setup(node, random)), numa_iteration)?;
+
+                // How many times longer a read of memory on the other node takes.
+                let local_ns = *local_ns.get_or_insert(result.nanoseconds_per_iteration());
+                let penalty = result.nanoseconds_per_iteration() / local_ns;
+                results.push(
+                    result
+                        .labelled("NUMA Read", LINE_SIZE)
+                        .with_parameter("access", access)
+                        .with_parameter("memory", placement)
+                        .with_parameter("node", &node.to_string())
+                        .with_metric("remote_penalty", penalty),
+                );
+            }
+        }
+        Ok(results)
+    }
+}
@@ -0,0 +1,304 @@
+use super::disk::create_file_to_read;
+use crate::cache::CacheMode;
+use crate::harness::BenchmarkResult;
+use crate::mmap::{Advice, Mapping};
+use crate::options::BenchmarkOptions;
+use crate::registry::{Benchmark, Category, Requirements};
+use failure::Error;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use std::fs;
+use std::mem;
+use std::ptr;
+
+// Memory the fault suites touch, a page at a time. They stop once they've touched all of it, since
+// unmapping it again to start over costs about as much as the faults.
+const PAGE_FAULT_MEMORY_SIZE: usize = n_gib_bytes!(1) as usize;
+const HUGE_PAGE_SIZE: usize = n_mib_bytes!(2) as usize;
+
+// Minor and major faults of the process so far, the way `syscall_getrusage` gets them.
+fn page_faults() -> (u64, u64) {
+    unsafe {
+        let mut usage: libc::rusage = mem::zeroed();
+        libc::getrusage(libc::RUSAGE_SELF, &mut usage);
+        (usage.ru_minflt as u64, usage.ru_majflt as u64)
+    }
+}
+
+pub struct PageFaultTest {
+    mapping: Mapping,
+    // Offsets of the pages to touch, in the order they're touched.
+    pages: Vec<usize>,
+    i: usize,
+    // Faults the process had taken when the setup was done.
+    faults: (u64, u64),
+}
+
+impl PageFaultTest {
+    fn new(mapping: Mapping, pages: Vec<usize>) -> PageFaultTest {
+        PageFaultTest {
+            mapping,
+            pages,
+            i: 0,
+            faults: page_faults(),
+        }
+    }
+
+    fn write(&mut self) -> bool {
+        unsafe {
+            ptr::write_volatile(self.mapping.as_mut_ptr().add(self.pages[self.i]), 1);
+        }
+        self.i += 1;
+        self.i < self.pages.len()
+    }
+
+    fn read(&mut self) -> bool {
+        unsafe {
+            ptr::read_volatile(self.mapping.as_ptr().add(self.pages[self.i]));
+        }
+        self.i += 1;
+        self.i < self.pages.len()
+    }
+
+    // Adds the faults the measurement took per iteration, which should be 1 if every touch faulted
+    // the way the benchmark meant it to, and the time per fault. Without any faults there's no
+    // time per fault to report, which is flagged instead.
+    fn finish(&self, result: BenchmarkResult, major: bool) -> BenchmarkResult {
+        let (minor_faults, major_faults) = page_faults();
+        let faults = if major {
+            major_faults - self.faults.1
+        } else {
+            minor_faults - self.faults.0
+        };
+        let kind = if major { "major" } else { "minor" };
+        let faults_per_iteration = faults as f64 / result.iterations as f64;
+        let result = result.with_metric(
+            &format!("{}_faults_per_iteration", kind),
+            faults_per_iteration,
+        );
+
+        if faults == 0 {
+            eprintln!(
+                "No {} page faults during the measurement, so there's no time per fault",
+                kind
+            );
+            return result;
+        }
+        let per_fault = result.duration.as_nanos() as f64 / faults as f64;
+        result.with_metric("per_fault_ns", per_fault)
+    }
+}
+
+pub struct MemoryPageFaultMinor;
+
+impl Benchmark for MemoryPageFaultMinor {
+    type State = PageFaultTest;
+
+    fn name(&self) -> &'static str {
+        "memory_page_fault_minor"
+    }
+
+    fn title(&self) -> &'static str {
+        "Minor Page Fault"
+    }
+
+    fn description(&self) -> &'static str {
+        "Writes the first byte of every page of 1 GiB of freshly mapped anonymous memory, one minor fault each."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["page_fault"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            memory: PAGE_FAULT_MEMORY_SIZE as u64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        page_size::get()
+    }
+
+    fn setup(&self, _options: &BenchmarkOptions) -> Result<PageFaultTest, Error> {
+        let mapping = Mapping::anonymous(PAGE_FAULT_MEMORY_SIZE).unwrap();
+        // With transparent huge pages set to `always`, a touch could fault in 2 MiB at once.
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::madvise(
+                mapping.as_ptr() as *mut _,
+                mapping.len(),
+                libc::MADV_NOHUGEPAGE,
+            );
+        }
+        let pages = (0..PAGE_FAULT_MEMORY_SIZE)
+            .step_by(page_size::get())
+            .collect();
+        Ok(PageFaultTest::new(mapping, pages))
+    }
+
+    fn iteration(&self, test: &mut PageFaultTest) -> bool {
+        test.write()
+    }
+
+    fn finish(&self, test: PageFaultTest, result: BenchmarkResult) -> BenchmarkResult {
+        test.finish(result, false)
+    }
+}
+
+// The setting in /sys/kernel/mm/transparent_hugepage/enabled, e.g. `madvise`.
+fn transparent_huge_pages() -> String {
+    fs::read_to_string("/sys/kernel/mm/transparent_hugepage/enabled")
+        .ok()
+        .and_then(|enabled| {
+            let start = enabled.find('[')?;
+            let end = enabled.find(']')?;
+            Some(enabled[start + 1..end].to_string())
+        })
+        .unwrap_or_else(|| String::from("unknown"))
+}
+
+pub struct MemoryPageFaultHuge;
+
+impl Benchmark for MemoryPageFaultHuge {
+    type State = PageFaultTest;
+
+    fn name(&self) -> &'static str {
+        "memory_page_fault_huge"
+    }
+
+    fn title(&self) -> &'static str {
+        "Transparent Huge Page Fault"
+    }
+
+    fn description(&self) -> &'static str {
+        "Writes the first byte of every 2 MiB of 1 GiB of anonymous memory advised with MADV_HUGEPAGE, one huge page fault each."
+    }
+
+    fn category(&self) -> Category {
+        Category::Memory
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["page_fault", "huge_pages"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            linux_only: true,
+            memory: (PAGE_FAULT_MEMORY_SIZE + HUGE_PAGE_SIZE) as u64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        HUGE_PAGE_SIZE
+    }
+
+    // With transparent huge pages disabled every iteration takes 512 minor faults instead.
+    fn parameters(&self, _options: &BenchmarkOptions) -> Vec<(&'static str, String)> {
+        vec![("thp", transparent_huge_pages())]
+    }
+
+    fn setup(&self, _options: &BenchmarkOptions) -> Result<PageFaultTest, Error> {
+        // Only whole, aligned huge pages can be backed by one, so the mapping gets the room to
+        // start at the next boundary.
+        let mapping = Mapping::anonymous(PAGE_FAULT_MEMORY_SIZE + HUGE_PAGE_SIZE).unwrap();
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::madvise(
+                mapping.as_ptr() as *mut _,
+                mapping.len(),
+                libc::MADV_HUGEPAGE,
+            );
+        }
+        let start = (HUGE_PAGE_SIZE - mapping.as_ptr() as usize % HUGE_PAGE_SIZE) % HUGE_PAGE_SIZE;
+        let pages = (start..start + PAGE_FAULT_MEMORY_SIZE)
+            .step_by(HUGE_PAGE_SIZE)
+            .collect();
+        Ok(PageFaultTest::new(mapping, pages))
+    }
+
+    fn iteration(&self, test: &mut PageFaultTest) -> bool {
+        test.write()
+    }
+
+    fn finish(&self, test: PageFaultTest, result: BenchmarkResult) -> BenchmarkResult {
+        test.finish(result, false)
+    }
+}
+
+pub struct DiskPageFaultMajor;
+
+impl Benchmark for DiskPageFaultMajor {
+    type State = PageFaultTest;
+
+    fn name(&self) -> &'static str {
+        "disk_page_fault_major"
+    }
+
+    fn title(&self) -> &'static str {
+        "Major Page Fault"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reads a byte of every page of a mapped 1 GiB file evicted from the page cache, in random order, one major fault each."
+    }
+
+    fn category(&self) -> Category {
+        Category::Disk
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["read", "random", "page_fault", "mmap"]
+    }
+
+    fn requirements(&self) -> Requirements {
+        Requirements {
+            memory: (PAGE_FAULT_MEMORY_SIZE / page_size::get() * 8) as u64,
+            disk: PAGE_FAULT_MEMORY_SIZE as u64,
+            ..Requirements::default()
+        }
+    }
+
+    fn bytes_per_iteration(&self, _options: &BenchmarkOptions) -> usize {
+        page_size::get()
+    }
+
+    fn setup(&self, options: &BenchmarkOptions) -> Result<PageFaultTest, Error> {
+        let path = options.scratch_file(self.name());
+        let file = create_file_to_read(
+            &path,
+            PAGE_FAULT_MEMORY_SIZE,
+            CacheMode::Cold,
+            options.drop_caches,
+        );
+        // Without MADV_RANDOM the kernel reads ahead around every fault, and most touches become
+        // minor faults on the pages it read.
+        let mapping = Mapping::new(&file, PAGE_FAULT_MEMORY_SIZE, false, false).unwrap();
+        mapping.advise(Advice::Random).unwrap();
+
+        let mut pages: Vec<usize> = (0..PAGE_FAULT_MEMORY_SIZE)
+            .step_by(page_size::get())
+            .collect();
+        pages.shuffle(&mut thread_rng());
+        Ok(PageFaultTest::new(mapping, pages))
+    }
+
+    fn iteration(&self, test: &mut PageFaultTest) -> bool {
+        test.read()
+    }
+
+    fn finish(&self, test: PageFaultTest, result: BenchmarkResult) -> BenchmarkResult {
+        test.finish(result, true)
+    }
+
+    fn teardown(&self, options: &BenchmarkOptions) {
+        let _ = fs::remove_file(options.scratch_file(self.name()));
+    }
+}
@@ -0,0 +1,164 @@
+use crate::harness::black_box;
+use crate::options::BenchmarkOptions;
+use crate::registry::{Benchmark, Category};
+use failure::Error;
+use std::fs;
+use std::process;
+use std::time::SystemTime;
+
+// this comes from the auxilirary vector on some OSes, making this not do a syscall.
+// on the linux kernel I've been testing on, it does do a syscall. on darwin, it doesn't.
+pub struct SyscallGetpid;
+
+impl Benchmark for SyscallGetpid {
+    type State = ();
+
+    fn name(&self) -> &'static str {
+        "syscall_getpid"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sycall getpid(2)"
+    }
+
+    fn description(&self) -> &'static str {
+        "Calls getpid(2), which some platforms answer without entering the kernel."
+    }
+
+    fn category(&self) -> Category {
+        Category::Syscall
+    }
+
+    fn setup(&self, _options: &BenchmarkOptions) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn iteration(&self, _: &mut ()) -> bool {
+        black_box(process::id());
+        true
+    }
+}
+
+// this is available in user-space memory (depending on libc) and often doesn't result in a sycall.
+pub struct SyscallTime;
+
+impl Benchmark for SyscallTime {
+    type State = ();
+
+    fn name(&self) -> &'static str {
+        "syscall_time"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sycall gettimeofday(2)"
+    }
+
+    fn description(&self) -> &'static str {
+        "Gets the wall clock time, which is usually served by the vDSO."
+    }
+
+    fn category(&self) -> Category {
+        Category::Syscall
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["vdso"]
+    }
+
+    fn setup(&self, _options: &BenchmarkOptions) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn iteration(&self, _: &mut ()) -> bool {
+        black_box(SystemTime::now());
+        true
+    }
+}
+
+// syscall, can't be optimized out
+pub struct SyscallGetrusage;
+
+impl Benchmark for SyscallGetrusage {
+    type State = libc::rusage;
+
+    fn name(&self) -> &'static str {
+        "syscall_getrusage"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sycall getrusage(2)"
+    }
+
+    fn description(&self) -> &'static str {
+        "Calls getrusage(2) for the current process."
+    }
+
+    fn category(&self) -> Category {
+        Category::Syscall
+    }
+
+    fn setup(&self, _options: &BenchmarkOptions) -> Result<libc::rusage, Error> {
+        let time = libc::timeval {
+            tv_sec: 0,
+            tv_usec: 0,
+        };
+        Ok(libc::rusage {
+            ru_utime: time,
+            ru_stime: time,
+            ru_maxrss: 0,
+            ru_ixrss: 0,
+            ru_idrss: 0,
+            ru_isrss: 0,
+            ru_minflt: 0,
+            ru_majflt: 0,
+            ru_nswap: 0,
+            ru_inblock: 0,
+            ru_oublock: 0,
+            ru_msgsnd: 0,
+            ru_msgrcv: 0,
+            ru_nsignals: 0,
+            ru_nvcsw: 0,
+            ru_nivcsw: 0,
+        })
+    }
+
+    fn iteration(&self, rusage: &mut libc::rusage) -> bool {
+        unsafe {
+            libc::getrusage(0, rusage);
+        }
+        true
+    }
+}
+
+// syscall, can't be optimized out
+pub struct SyscallStat;
+
+impl Benchmark for SyscallStat {
+    type State = fs::File;
+
+    fn name(&self) -> &'static str {
+        "syscall_stat"
+    }
+
+    fn title(&self) -> &'static str {
+        "Sycall stat(2)"
+    }
+
+    fn description(&self) -> &'static str {
+        "Stats an open file descriptor with fstat(2)."
+    }
+
+    fn category(&self) -> Category {
+        Category::Syscall
+    }
+
+    fn setup(&self, _options: &BenchmarkOptions) -> Result<fs::File, Error> {
+        Ok(fs::File::open("/tmp")?)
+    }
+
+    fn iteration(&self, f: &mut fs::File) -> bool {
+        let metadata = f.metadata().unwrap();
+        black_box(metadata);
+        true
+    }
+}
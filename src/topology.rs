@@ -0,0 +1,144 @@
+#[cfg(target_os = "linux")]
+use failure::format_err;
+use failure::Error;
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::io;
+#[cfg(target_os = "linux")]
+use std::mem;
+
+// The cpus this process may run on, e.g. fewer than the machine has in a container limited with
+// cpusets.
+#[cfg(target_os = "linux")]
+pub fn available_cpus() -> Vec<usize> {
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        if libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return vec![0];
+        }
+        (0..libc::CPU_SETSIZE as usize)
+            .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+            .collect()
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn available_cpus() -> Vec<usize> {
+    let cpus = std::thread::available_parallelism().map_or(1, |cpus| cpus.get());
+    (0..cpus).collect()
+}
+
+// Keeps the calling thread on `cpu`, so the scheduler doesn't move it away from its caches and
+// memory mid-benchmark.
+pub fn pin_to_cpu(cpu: usize) -> Result<(), Error> {
+    pin_to_cpus(&[cpu])
+}
+
+// Keeps the calling thread on any of `cpus`, e.g. the cpus of a NUMA node, or all of
+// `available_cpus` to undo `pin_to_cpu`.
+#[cfg(target_os = "linux")]
+pub fn pin_to_cpus(cpus: &[usize]) -> Result<(), Error> {
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        for &cpu in cpus {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        if libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(format_err!(
+                "failed to pin thread to cpus {:?}: {}",
+                cpus,
+                io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(())
+}
+
+// macOS only takes affinity hints, so threads aren't pinned there.
+#[cfg(not(target_os = "linux"))]
+pub fn pin_to_cpus(_cpus: &[usize]) -> Result<(), Error> {
+    Ok(())
+}
+
+// A NUMA node: memory and the cpus closest to it.
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub id: usize,
+    pub cpus: Vec<usize>,
+}
+
+// The NUMA nodes with both cpus and memory, from /sys/devices/system/node. Machines without NUMA
+// have a single node, or none where /sys doesn't list them, e.g. on macOS.
+pub fn numa_nodes() -> Vec<Node> {
+    let read = |path: &str| fs::read_to_string(path).ok();
+    let with_memory = read("/sys/devices/system/node/has_memory")
+        .map(|nodes| parse_cpu_list(&nodes))
+        .unwrap_or_default();
+
+    let mut nodes: Vec<Node> = with_memory
+        .into_iter()
+        .filter_map(|id| {
+            let cpus = read(&format!("/sys/devices/system/node/node{}/cpulist", id))?;
+            Some(Node {
+                id,
+                cpus: parse_cpu_list(&cpus),
+            })
+        })
+        .filter(|node| !node.cpus.is_empty())
+        .collect();
+    nodes.sort_by_key(|node| node.id);
+    nodes
+}
+
+// Parses lists like `0-3,8-11` the kernel uses for sets of cpus and nodes.
+fn parse_cpu_list(list: &str) -> Vec<usize> {
+    let mut cpus = vec![];
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        let mut bounds = range.splitn(2, '-');
+        let start: Option<usize> = bounds.next().and_then(|bound| bound.parse().ok());
+        let end = bounds.next().map_or(start, |bound| bound.parse().ok());
+        if let (Some(start), Some(end)) = (start, end) {
+            cpus.extend(start..=end);
+        }
+    }
+    cpus
+}
+
+// Allocates the pages of memory that hasn't been touched yet on `node`, whichever cpu touches
+// them. libc doesn't have mbind, so it's called through syscall with the constants from
+// <linux/mempolicy.h>.
+#[cfg(target_os = "linux")]
+pub fn bind_to_node(memory: &[u8], node: usize) -> Result<(), Error> {
+    const MPOL_BIND: libc::c_long = 2;
+    const MPOL_MF_STRICT: libc::c_long = 1;
+
+    let bits = 8 * mem::size_of::<libc::c_ulong>();
+    let mut nodemask: Vec<libc::c_ulong> = vec![0; node / bits + 1];
+    nodemask[node / bits] |= 1 << (node % bits);
+
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            memory.as_ptr(),
+            memory.len(),
+            MPOL_BIND,
+            nodemask.as_ptr(),
+            // The kernel drops the last bit of the mask, so it gets one more than there are.
+            (nodemask.len() * bits + 1) as libc::c_ulong,
+            MPOL_MF_STRICT,
+        )
+    };
+    if result != 0 {
+        return Err(format_err!(
+            "failed to bind memory to node {}: {}",
+            node,
+            io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn bind_to_node(_memory: &[u8], _node: usize) -> Result<(), Error> {
+    Ok(())
+}
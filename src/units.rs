@@ -0,0 +1,122 @@
+use byte_unit::Byte;
+use std::time::Duration;
+
+// TODO handle less than 1ns
+pub fn format_nanoseconds(nanoseconds: f64) -> String {
+    if nanoseconds <= 10.0 {
+        format!("{:.3} ns", nanoseconds)
+    } else {
+        get_appropriate_time_unit(Duration::from_nanos(nanoseconds as u64))
+    }
+}
+
+// Picks the unit that makes the duration readable, e.g. 1500 μs is shown as 1 ms.
+pub fn get_appropriate_time_unit(duration: Duration) -> String {
+    if duration.as_nanos() < 1000 {
+        format!("{} ns", duration.as_nanos())
+    } else if duration.as_nanos() > 1000 && duration.as_millis() < 5 {
+        format!("{} μs", duration.as_micros())
+    } else if duration.as_micros() > 1000 && duration.as_millis() < 3000 {
+        format!("{} ms", duration.as_millis())
+    } else if duration.as_secs() <= 120 {
+        format!("{:.2} s", duration.as_millis() as f64 / 1000.0)
+    } else if duration.as_secs() <= 3600 {
+        format!("{:.2} min", (duration.as_secs() as f64) / 60.0)
+    } else {
+        format!("{:.2} hours", (duration.as_secs() as f64) / 3600.0)
+    }
+}
+
+// Durations are recognized by their name ending in `_ns`, and throughputs by `_bytes_per_second`,
+// see `BenchmarkResult::metrics`.
+pub fn format_metric(name: &str, value: f64) -> String {
+    if name.ends_with("_ns") {
+        format_nanoseconds(value)
+    } else if name.ends_with("_bytes_per_second") {
+        format!(
+            "{}/s",
+            Byte::from_bytes(value as u128)
+                .get_appropriate_unit(true)
+                .format(2)
+        )
+    } else if value.fract() == 0.0 || value >= 100.0 {
+        format!("{:.0}", value)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+// What to show a metric as, its unit shows in the value already.
+pub fn metric_label(name: &str) -> &str {
+    name.trim_end_matches("_ns")
+        .trim_end_matches("_bytes_per_second")
+}
+
+// Like `Byte::get_appropriate_unit`, but 1 GiB is shown as 1 GiB rather than 1024 MiB.
+pub fn format_bytes(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.0} {}", value, units[unit])
+}
+
+// Rounds to a single significant digit, e.g. 47 => 50 and 0.34 => 0.3, since that's all the
+// precision napkin math needs.
+pub fn round_napkin(value: f64) -> f64 {
+    if value <= 0.0 || !value.is_finite() {
+        return value;
+    }
+    let magnitude = 10f64.powi(value.log10().floor() as i32);
+    (value / magnitude).round() * magnitude
+}
+
+pub fn format_napkin(value: f64, unit: &str) -> String {
+    let decimals = if value >= 1.0 || value <= 0.0 {
+        0
+    } else {
+        -value.log10().floor() as usize
+    };
+    format!("{:.*} {}", decimals, value, unit)
+}
+
+pub fn napkin_duration(nanoseconds: f64) -> String {
+    let nanoseconds = round_napkin(nanoseconds);
+    let units = [
+        ("ns", 1.0),
+        ("μs", 1e3),
+        ("ms", 1e6),
+        ("s", 1e9),
+        ("min", 60e9),
+        ("hours", 3600e9),
+    ];
+
+    // Minutes only kick in after two of them, same as `get_appropriate_time_unit`.
+    let (unit, scale) = units
+        .iter()
+        .rev()
+        .find(|(unit, scale)| match *unit {
+            "min" => nanoseconds >= 120e9,
+            _ => nanoseconds >= *scale,
+        })
+        .unwrap_or(&units[0]);
+
+    format_napkin(round_napkin(nanoseconds / scale), unit)
+}
+
+pub fn napkin_throughput(bytes_per_second: f64) -> String {
+    let units = ["B/s", "KiB/s", "MiB/s", "GiB/s", "TiB/s"];
+    let mut value = round_napkin(bytes_per_second);
+    let mut unit = 0;
+
+    // Step up while the rounded value still doesn't fit, so 1000 MiB/s becomes 1 GiB/s.
+    while round_napkin(value) >= 1000.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format_napkin(round_napkin(value), units[unit])
+}